@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use gxter::GXTFile;
+
+// any input may be rejected, but none may cause a panic
+fuzz_target!(|data: &[u8]| {
+    let _ = GXTFile::read_from_gxt(&mut std::io::Cursor::new(data), &None, &None, &None);
+    let _ = GXTFile::read_from_gxt_strict(&mut std::io::Cursor::new(data), &None, &None, &None);
+});
@@ -0,0 +1,136 @@
+use gxter::{GXTCharacterTable, GXTFile, ImportOrdering, WriteOptions};
+use getopts::Matches;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// a file to convert, and the file it's converted into
+struct BatchJob {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+// lists the files to convert: files given directly, and files in the given directories (and
+// their subdirectories) that match one of the patterns. the output files keep their location
+// relative to the directory they were found in
+fn find_jobs(inputs: &[String], patterns: &GlobSet, output_dir: &Path, extension: &str) -> Vec<BatchJob> {
+
+    let mut res: Vec<BatchJob> = vec!();
+
+    for input in inputs.iter().map(Path::new) {
+        if input.is_dir() {
+            for entry in WalkDir::new(input).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+                let relative = entry.path().strip_prefix(input).unwrap_or(entry.path());
+                if entry.file_type().is_file() && patterns.is_match(relative) {
+                    res.push(BatchJob { input: entry.path().to_path_buf(), output: output_dir.join(relative).with_extension(extension) });
+                }
+            }
+        } else {
+            let name = input.file_name().map(Path::new).unwrap_or(input);
+            res.push(BatchJob { input: input.to_path_buf(), output: output_dir.join(name).with_extension(extension) });
+        }
+    }
+
+    res
+}
+
+// converts a single file, returning a description of whatever went wrong
+fn convert(job: &BatchJob, decompile: bool, options: &WriteOptions, ordering: &Option<ImportOrdering>, name_list: &Option<HashMap<u32,String>>) -> Result<(), String> {
+
+    let _f = File::open(&job.input).map_err(|e| e.to_string())?;
+    let mut file = BufReader::new(_f);
+
+    let gxt = match decompile {
+        true => GXTFile::read_from_gxt(&mut file, ordering, options.custom_table, name_list),
+        false => GXTFile::read_from_text(&mut file),
+    }.map_err(|e| e.to_string())?;
+
+    if !decompile {
+        // warnings don't stop the file from being compiled, and are left out to keep the summary short
+        let errors: Vec<String> = gxt.validate(options).iter().filter(|i| i.is_error()).map(|i| i.to_string()).collect();
+        if !errors.is_empty() {
+            return Err(format!("{} error(s) found:\n    {}", errors.len(), errors.join("\n    ")));
+        }
+    }
+
+    if let Some(dir) = job.output.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut outfile = File::create(&job.output).map_err(|e| e.to_string())?;
+    match decompile {
+        true => gxt.write_to_text(&mut outfile),
+        false => gxt.write_to_gxt_with_options(&mut outfile, options).map(|_| ()),
+    }.map_err(|e| e.to_string())
+}
+
+/// Converts many files at once, in parallel: files given directly, and files matching the
+/// `--glob` patterns in the given directories. A file failing to convert doesn't stop the
+/// others; every failure is listed at the end, along with the number of files converted.
+pub fn batch(args: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let Some(output_dir) = matches.opt_str("output-dir") else {
+        eprintln!("The batch mode requires an output directory!");
+        return Ok(());
+    };
+    if args.is_empty() {
+        eprintln!("The batch mode requires at least one file or directory!");
+        return Ok(());
+    }
+
+    let decompile = matches.opt_present("d");
+    let (default_pattern, extension) = match decompile {
+        true => ("*.gxt", "toml"),
+        false => ("*.toml", "gxt"),
+    };
+
+    let mut patterns = matches.opt_strs("glob");
+    if patterns.is_empty() {
+        patterns.push(default_pattern.to_string());
+    }
+    let mut builder = GlobSetBuilder::new();
+    for p in &patterns {
+        match Glob::new(p) {
+            Ok(g) => { builder.add(g); },
+            Err(e) => {
+                eprintln!("Invalid pattern {p}: {e}");
+                std::process::exit(1);
+            },
+        }
+    }
+    let patterns = builder.build().expect("Unable to build the file patterns");
+
+    let options = crate::compile::write_options(matches, custom_table)?;
+    let jobs = find_jobs(args, &patterns, Path::new(&output_dir), extension);
+
+    // files with the same name in different directories given on the command line would be
+    // converted into the same file, so none of them is converted
+    let mut outputs: HashMap<&Path, usize> = HashMap::new();
+    for job in &jobs {
+        *outputs.entry(&job.output).or_default() += 1;
+    }
+
+    let results: Vec<Result<(), String>> = jobs.par_iter()
+        .map(|job| match outputs[job.output.as_path()] {
+            1 => convert(job, decompile, &options, ordering, name_list),
+            _ => Err(format!("{} is also the output of another file", job.output.display())),
+        })
+        .collect();
+
+    let mut failures = 0;
+    for (job, res) in jobs.iter().zip(&results) {
+        if let Err(e) = res {
+            eprintln!("{}: {e}", job.input.display());
+            failures += 1;
+        }
+    }
+    println!("{} file(s) converted, {} failed.", jobs.len() - failures, failures);
+
+    if failures > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use crate::input::read_gxt_or_text;
+use gxter::ImportOrdering;
+use gxter::project::{GXTLanguage, GXTProject, read_project_manifest};
+use getopts::Matches;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+/// Compiles every language listed in a project manifest. The languages are first checked for
+/// strings and tables that are missing from some of them (which are only warnings, unless
+/// `--strict` is given), and then validated. A language with errors is skipped, and a language
+/// that fails to compile or can't be written doesn't stop the others; the skipped and failed
+/// languages are listed at the end. Paths in the manifest are relative to the manifest itself.
+pub fn build(args: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let [manifest_filename] = args else {
+        eprintln!("The build mode requires a project manifest!");
+        return Ok(());
+    };
+
+    let _f = File::open(manifest_filename)?;
+    let mut file = BufReader::new(_f);
+    let manifest = read_project_manifest(&mut file)?;
+
+    let base = Path::new(manifest_filename).parent().unwrap_or(Path::new(""));
+    let mut project = GXTProject::default();
+
+    for (language, l) in &manifest.languages {
+        let custom_table = match &l.character_table {
+            Some(name) => {
+                let _f = File::open(base.join(name))?;
+                let mut file = BufReader::new(_f);
+
+                Some(gxter::read_custom_table(&mut file)?)
+            },
+            None => None,
+        };
+        let source = base.join(&l.source);
+        let file = read_gxt_or_text(&source.to_string_lossy(), ordering, &custom_table, name_list)?;
+        project.languages.insert(language.clone(), GXTLanguage { file, custom_table });
+    }
+
+    // a partly translated project can still be built, unless asked otherwise
+    let strict = matches.opt_present("strict");
+    let mut skipped: HashSet<String> = HashSet::new();
+
+    for i in project.check_parity() {
+        match strict {
+            true => {
+                eprintln!("{i}");
+                skipped.insert(i.language);
+            },
+            false => { eprintln!("warning: {i}"); },
+        }
+    }
+
+    // every language gets its own character table, so none is given here
+    let options = crate::compile::write_options(matches, &None)?;
+    for (language, i) in project.validate(&options) {
+        eprintln!("{language}: {}", crate::compile::describe_issue(&i));
+        if i.is_error() {
+            skipped.insert(language);
+        }
+    }
+
+    // languages with errors are left out, and keep their old files
+    project.languages.retain(|language, _| !skipped.contains(language));
+
+    // a language failing to compile doesn't stop the others, and leaves its old file untouched
+    let mut failures: Vec<(String, gxter::GXTError)> = vec!();
+    for (language, res) in project.compile_all(&options) {
+        let output = base.join(&manifest.languages[&language].output);
+        let written = res.and_then(|data| {
+            if let Some(dir) = output.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            File::create(&output)?.write_all(&data)?;
+            Ok(())
+        });
+        match written {
+            Ok(()) => { println!("{language}: {}", output.display()); },
+            Err(e) => { failures.push((language, e)); },
+        }
+    }
+
+    for (language, e) in &failures {
+        eprintln!("{language}: {e}");
+    }
+    // listed in the manifest's order
+    let skipped: Vec<&String> = manifest.languages.keys().filter(|l| skipped.contains(*l)).collect();
+    if !skipped.is_empty() {
+        eprintln!("{} language(s) skipped because of errors: {}", skipped.len(), skipped.iter().map(|l| l.as_str()).collect::<Vec<_>>().join(", "));
+    }
+    if !failures.is_empty() {
+        eprintln!("{} language(s) failed to compile.", failures.len());
+    }
+    if !skipped.is_empty() || !failures.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
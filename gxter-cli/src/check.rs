@@ -0,0 +1,29 @@
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use std::collections::HashMap;
+
+/// Compares a translation against its source file and prints every inconsistency found. Exits with
+/// a non-zero status if there were any, so it can be used in scripts.
+pub fn check(args: &[String], ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let [source_filename, translation_filename] = args else {
+        eprintln!("The check mode requires a source file and a translated file!");
+        return Ok(());
+    };
+
+    // the custom table only applies to the translation, the source is expected to be a stock file
+    let source = read_gxt_or_text(source_filename, ordering, &None, name_list)?;
+    let translation = read_gxt_or_text(translation_filename, ordering, custom_table, name_list)?;
+
+    let issues = gxter::consistency::check_translation(&source, &translation);
+
+    for i in &issues {
+        println!("{i}");
+    }
+
+    if !issues.is_empty() {
+        eprintln!("{} inconsistencies found", issues.len());
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use gxter::{GXTCharacterTable, GXTFile, WriteOptions};
+use gxter::validation::{GXTLimitProfile, GXTValidationIssue};
+use getopts::Matches;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+/// Creates a file for writing, or writes to standard output if the name is `-`.
+pub fn create_output(filename: &str) -> io::Result<Box<dyn Write>> {
+    match filename {
+        "-" => Ok(Box::new(BufWriter::new(io::stdout().lock()))),
+        _ => Ok(Box::new(File::create(filename)?)),
+    }
+}
+
+/// Reads the options that control how text is compiled from the command line. Exits if any of
+/// them has an unknown value.
+pub fn write_options<'a>(matches: &Matches, custom_table: &'a Option<GXTCharacterTable>) -> Result<WriteOptions<'a>, gxter::GXTError> {
+
+    let mut fallback: Option<gxter::GXTFallbackPolicy> = match matches.opt_str("transliterate") {
+        Some(name) => {
+            let _f = File::open(&name)?;
+            let mut file = BufReader::new(_f);
+
+            Some(gxter::read_fallback_policy(&mut file)?)
+        },
+        None => None,
+    };
+    if matches.opt_present("strip-diacritics") {
+        fallback.get_or_insert_default().strip_diacritics = true;
+    }
+
+    let normalization = match matches.opt_str("normalize").as_deref() {
+        None => None,
+        Some("nfc") => Some(gxter::NormalizationForm::NFC),
+        Some("nfd") => Some(gxter::NormalizationForm::NFD),
+        Some("nfkc") => Some(gxter::NormalizationForm::NFKC),
+        Some("nfkd") => Some(gxter::NormalizationForm::NFKD),
+        Some("auto") => Some(gxter::NormalizationForm::Auto),
+        Some(x) => {
+            eprintln!("Unknown normalization form: {x}");
+            std::process::exit(1);
+        },
+    };
+
+    let profile = match matches.opt_str("limits").as_deref() {
+        None => None,
+        Some("three") => Some(GXTLimitProfile::Three),
+        Some("vice") => Some(GXTLimitProfile::Vice),
+        Some("sa-pc") => Some(GXTLimitProfile::SanPc),
+        Some("sa-mobile") => Some(GXTLimitProfile::SanMobile),
+        Some(x) => {
+            eprintln!("Unknown limit profile: {x}");
+            std::process::exit(1);
+        },
+    };
+    // the estimates are unverified, so they're only used when asked for
+    let mut limits = profile.map(|p| match matches.opt_present("estimated-limits") {
+        true => p.estimated_limits(),
+        false => p.limits(),
+    });
+    if let Some(name) = matches.opt_str("limit-file") {
+        let _f = File::open(&name)?;
+        let mut file = BufReader::new(_f);
+
+        let custom = gxter::validation::read_limits(&mut file)?;
+        limits = Some(limits.unwrap_or_default().override_with(&custom));
+    }
+
+    Ok(WriteOptions {
+        custom_table,
+        fallback,
+        normalization,
+        limits,
+    })
+}
+
+/// Describes a problem found by the validator, marking it as a warning if it doesn't stop the
+/// file from being compiled.
+pub fn describe_issue(issue: &GXTValidationIssue) -> String {
+    match issue.is_error() {
+        true => issue.to_string(),
+        false => format!("warning: {issue}"),
+    }
+}
+
+/// Checks a file and compiles it into a GXT file, printing every problem found, as well as every
+/// character that had to be replaced. If there were any errors (rather than just warnings),
+/// nothing is written and false is returned.
+pub fn compile(gxt: &GXTFile, options: &WriteOptions, show_shadowed: bool, output_filename: &str) -> Result<bool, gxter::GXTError> {
+
+    // report every problem at once, instead of only the first one write_to_gxt finds
+    let issues = gxt.validate(options);
+    for i in &issues {
+        eprintln!("{}", describe_issue(i));
+    }
+    let errors = issues.iter().filter(|i| i.is_error()).count();
+    if errors > 0 {
+        eprintln!("{errors} error(s) found, the file was not compiled.");
+        return Ok(false);
+    }
+
+    if show_shadowed {
+        for k in gxt.shadowed_keys() {
+            eprintln!("{k}");
+        }
+    }
+
+    // an error here is returned rather than a panic, so that the watch mode can keep going if the
+    // game is holding the file open
+    let mut outfile = create_output(output_filename)?;
+    let report = gxt.write_to_gxt_with_options(&mut outfile, options)?;
+    outfile.flush()?;
+
+    for (table, keys) in &report.substitutions {
+        for (key, substitutions) in keys {
+            for e in substitutions {
+                eprintln!("[{table}] {key}: replaced {:?} at position {} with {:?}", e.original, e.position, e.replacement);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Writes the result of a mode that produces a new file (like merging) into the file specified by
+/// `-o`: a GXT file, or a text file (possibly on the screen) when decompiling. Exits if the file
+/// can't be compiled.
+pub fn write_result(gxt: &GXTFile, matches: &Matches, custom_table: &Option<GXTCharacterTable>) -> Result<(), gxter::GXTError> {
+
+    let output = matches.opt_str("o");
+    if matches.opt_present("d") {
+        match output {
+            Some(ofn) => {
+                let mut outfile = create_output(&ofn)?;
+                gxt.write_to_text(&mut outfile)?;
+            },
+            None => {
+                gxt.write_to_text(&mut io::stdout())?;
+            },
+        }
+    } else {
+        let Some(ofn) = output else {
+            eprintln!("No output file name specified! (Use -o - to write to standard output.)");
+            return Ok(());
+        };
+        let options = write_options(matches, custom_table)?;
+        if !compile(gxt, &options, matches.opt_present("shadowed"), &ofn)? {
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use std::collections::HashMap;
+
+/// Compares two GXT or text files and prints the strings and tables that differ, as text, colored
+/// text or JSON. Exits with a non-zero status if there were any differences, like `diff` does.
+pub fn diff(args: &[String], output_format: &Option<String>, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let [old_filename, new_filename] = args else {
+        eprintln!("The diff mode requires an old file and a new file!");
+        return Ok(());
+    };
+
+    let old = read_gxt_or_text(old_filename, ordering, custom_table, name_list)?;
+    let new = read_gxt_or_text(new_filename, ordering, custom_table, name_list)?;
+
+    let d = gxter::diff::diff(&old, &new, name_list);
+
+    match output_format.as_deref() {
+        None | Some("text") => { print!("{d}"); },
+        Some("json") => { println!("{}", serde_json::to_string_pretty(&d).expect("Unable to serialize the differences")); },
+#[cfg(feature = "pretty")]
+        Some("color") => { crate::pretty::print_diff(&d); },
+        Some(x) => {
+            eprintln!("Unknown output format: {x}");
+            std::process::exit(1);
+        },
+    }
+
+    if !d.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use gxter::inference;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+
+/// Infers a character table from a GXT file and a reference text file, then writes it to the
+/// output file (or on screen). Conflicts and unusable reference strings are reported on stderr.
+pub fn infer_table(args: &[String], output: Option<String>) -> Result<(), gxter::GXTError> {
+
+    let [gxt_filename, reference_filename] = args else {
+        eprintln!("The infer-table mode requires a GXT file and a reference text file!");
+        return Ok(());
+    };
+
+    let _f = File::open(reference_filename).expect("Unable to open reference text file");
+    let mut file = BufReader::new(_f);
+    let reference = inference::read_reference_text(&mut file)?;
+
+    let _f = File::open(gxt_filename).expect("Unable to open GXT file");
+    let mut file = BufReader::new(_f);
+    let result = inference::infer_character_table(&mut file, &reference)?;
+
+    for s in &result.skipped {
+        eprintln!("Skipped [{}] {}: {}", s.table, s.key, s.reason);
+    }
+
+    for c in &result.conflicts {
+        eprintln!("Conflict for code {}, using '{}':", c.code, c.chosen);
+        for candidate in &c.candidates {
+            let locations: Vec<String> = candidate.locations.iter().map(|(t,k)| format!("[{t}] {k}")).collect();
+            eprintln!("\t'{}' ({} times): {}", candidate.character, candidate.locations.len(), locations.join(", "));
+        }
+    }
+
+    match output {
+        Some(ofn) => {
+            let mut outfile = File::create(ofn).expect("Unable to open output file");
+            gxter::write_custom_table(&result.table, &mut outfile)?;
+        },
+        None => {
+            let mut stdout = io::stdout();
+            gxter::write_custom_table(&result.table, &mut stdout)?;
+        }
+    };
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use gxter::{GXTCharacterTable, GXTFile, ImportOrdering};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek};
+
+/// Something a GXT or text file can be read from: a file, or standard input.
+pub trait Input: BufRead + Seek {}
+impl<T: BufRead + Seek> Input for T {}
+
+/// Opens a file for reading, or standard input if the name is `-`. Standard input may be a pipe,
+/// which can't seek, so it's read whole into memory first.
+pub fn open_input(filename: &str) -> io::Result<Box<dyn Input>> {
+    if filename == "-" {
+        let mut data: Vec<u8> = vec!();
+        io::stdin().lock().read_to_end(&mut data)?;
+        return Ok(Box::new(Cursor::new(data)));
+    }
+    Ok(Box::new(BufReader::new(File::open(filename)?)))
+}
+
+/// Reads a GXT file or a text file, determining which one it is from its first four bytes (every
+/// GXT format starts with a known header, whereas text files can't start with one).
+pub fn read_gxt_or_text(filename: &str, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<GXTFile, gxter::GXTError> {
+
+    let mut file = open_input(filename)?;
+
+    let mut first_four_bytes: [u8; 4] = [0;4];
+    let is_gxt = match file.read_exact(&mut first_four_bytes) {
+        Ok(_) => matches!(&first_four_bytes, b"TKEY" | b"TABL" | b"\x04\0\x08\0" | b"\x04\0\x10\0"),
+        Err(_) => false, // too short to be a GXT file
+    };
+    file.rewind()?;
+
+    if is_gxt {
+        GXTFile::read_from_gxt(&mut file, ordering, custom_table, name_list)
+    } else {
+        GXTFile::read_from_text(&mut file)
+    }
+}
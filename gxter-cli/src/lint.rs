@@ -0,0 +1,30 @@
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use std::collections::HashMap;
+
+/// Lints every file given as an argument and prints the problems found. Exits with a non-zero
+/// status if there were any, so it can be used in scripts.
+pub fn lint(args: &[String], ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    if args.is_empty() {
+        eprintln!("The lint mode requires at least one GXT or text file!");
+        return Ok(());
+    }
+
+    let mut problem_count: usize = 0;
+
+    for filename in args {
+        let gxt = read_gxt_or_text(filename, ordering, custom_table, name_list)?;
+
+        for d in gxt.lint() {
+            println!("{filename}: {d}");
+            problem_count += 1;
+        }
+    }
+
+    if problem_count > 0 {
+        eprintln!("{problem_count} problem(s) found");
+        std::process::exit(1);
+    }
+    Ok(())
+}
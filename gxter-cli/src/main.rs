@@ -9,13 +9,34 @@ use std::env; //for env::args()
 
 #[cfg(feature = "pretty")] 
 mod pretty;
+mod batch;
+mod build;
+mod check;
+mod compile;
+mod diff;
+mod infer;
+mod input;
+mod lint;
+mod merge;
+mod patch;
+mod search;
+mod stats;
+mod watch;
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} FILE [options]", program);
+    let brief = format!("Usage: {0} FILE [options]\n       {0} infer-table GXT_FILE REFERENCE_FILE [options]\n       {0} lint FILE... [options]\n       {0} check SOURCE_FILE TRANSLATED_FILE [options]\n       {0} merge BASE_FILE FILE... -o OUTPUT [options]\n       {0} diff OLD_FILE NEW_FILE [options]\n       {0} make-patch OLD_FILE NEW_FILE [options]\n       {0} apply-patch BASE_FILE PATCH_FILE... -o OUTPUT [options]\n       {0} grep PATTERN FILE... [options]\n       {0} replace PATTERN REPLACEMENT FILE -o OUTPUT [options]\n       {0} stats FILE [SOURCE_FILE] [options]\n       {0} build MANIFEST [options]\n       {0} batch FILE_OR_DIRECTORY... --output-dir DIRECTORY [options]\n       {0} watch FILE... -o OUTPUT [options]", program);
     print!("{}", opts.usage(&brief));
 }
 
-fn main() -> Result<(), gxter::GXTError> {
+fn main() {
+    // errors are printed using their Display implementation, which includes their location
+    if let Err(e) = run() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), gxter::GXTError> {
 
     let mut opts = Options::new();
     opts.optflag("d","decompile","decompile a .gxt file into a text file, rather than the other way around");
@@ -26,6 +47,22 @@ fn main() -> Result<(), gxter::GXTError> {
 
     opts.optopt("o","output","output file name","NAME");
     opts.optopt("c","character-table","custom character table","FILENAME");
+    opts.optopt("t","transliterate","when compiling, replace characters that can't be encoded according to this fallback policy file","FILENAME");
+    opts.optflag("s","strip-diacritics","when compiling, remove diacritics from characters that can't be encoded");
+    opts.optopt("N","normalize","when compiling, convert strings to a Unicode normalization form first (nfc, nfd, nfkc, nfkd or auto)","FORM");
+    opts.optopt("l","limits","when compiling, check the limits of a game engine (three, vice, sa-pc or sa-mobile)","PROFILE");
+    opts.optflag("","estimated-limits","with --limits, also check unverified estimates of the game's buffer sizes");
+    opts.optopt("","limit-file","when compiling, check the limits listed in this file (overriding the ones from --limits)","FILENAME");
+    opts.optflag("","shadowed","when compiling, list strings in auxiliary tables that are shadowed by the main table");
+    opts.optopt("","conflicts","when merging, keep this version of strings that differ: theirs (the default), ours or fail","VERSION");
+    opts.optflag("","whole-tables","when merging, resolve conflicts for whole tables instead of single strings");
+    opts.optopt("","output-format","in the diff and stats modes, print the results as text (the default), json or (diff only, with the pretty feature) color","FORMAT");
+    opts.optopt("","output-dir","in the batch mode, write the converted files into this directory","DIRECTORY");
+    opts.optmulti("","glob","in the batch mode, only convert files in directories that match this pattern (may be given several times)","PATTERN");
+    opts.optmulti("","table","in the grep and replace modes, only look at this table (may be given several times)","NAME");
+    opts.optflag("","keys","in the grep mode, match string names instead of their values");
+    opts.optflag("","include-tags","in the replace mode, replace text inside tags as well");
+    opts.optflag("S","strict","when decompiling, fail if the file's tables or strings aren't sorted the way the games expect; in the build mode, skip languages with missing strings or tables");
     opts.optflag("K","key-sort","arrange strings in the same order as their keys");
     opts.optflag("O","offset-sort","arrange strings in the same order as their data locations");
     opts.optflag("h","help","print this help menu");
@@ -68,7 +105,7 @@ fn main() -> Result<(), gxter::GXTError> {
             let _f = File::open(&name).expect("Unable to open character table file");
             let mut file = BufReader::new(_f);
 
-            Some(gxter::read_custom_table(&mut file).unwrap())
+            Some(gxter::read_custom_table(&mut file)?)
         },
         None => None,
     };
@@ -78,23 +115,39 @@ fn main() -> Result<(), gxter::GXTError> {
             let _f = File::open(&name).expect("Unable to open name list file");
             let mut file = BufReader::new(_f);
 
-            Some(gxter::read_name_list(&mut file).unwrap())
+            Some(gxter::read_name_list(&mut file)?)
         },
         None => None,
     };
+
+    // the first free argument may name a mode other than compiling or decompiling a single file
+    match input_filename.as_str() {
+        "infer-table" => { return infer::infer_table(&matches.free[1..], matches.opt_str("output")); },
+        "check" => { return check::check(&matches.free[1..], &Some(data_ordering), &custom_table, &name_list); },
+        "lint" => { return lint::lint(&matches.free[1..], &Some(data_ordering), &custom_table, &name_list); },
+        "diff" => { return diff::diff(&matches.free[1..], &matches.opt_str("output-format"), &Some(data_ordering), &custom_table, &name_list); },
+        "make-patch" => { return patch::make_patch(&matches.free[1..], matches.opt_str("output"), &Some(data_ordering), &custom_table, &name_list); },
+        "apply-patch" => { return patch::apply_patch(&matches.free[1..], &matches, &Some(data_ordering), &custom_table, &name_list); },
+        "grep" => { return search::grep(&matches.free[1..], &matches, &Some(data_ordering), &custom_table, &name_list); },
+        "replace" => { return search::replace(&matches.free[1..], &matches, &Some(data_ordering), &custom_table, &name_list); },
+        "stats" => { return stats::stats(&matches.free[1..], &matches.opt_str("output-format"), &Some(data_ordering), &custom_table, &name_list); },
+        "build" => { return build::build(&matches.free[1..], &matches, &Some(data_ordering), &name_list); },
+        "batch" => { return batch::batch(&matches.free[1..], &matches, &Some(data_ordering), &custom_table, &name_list); },
+        "watch" => { return watch::watch(&matches.free[1..], &matches, &Some(data_ordering), &name_list); },
+        "merge" => { return merge::merge(&matches.free[1..], &matches, &Some(data_ordering), &custom_table, &name_list); },
+        _ => {},
+    }
     
 #[cfg(feature = "pretty")] 
     if do_pretty_print {
         let gxt = if decompile {
-            let _f = File::open(&input_filename).expect("Unable to open GXT file");
-            let mut file = BufReader::new(_f);
+            let mut file = input::open_input(&input_filename)?;
 
-            GXTFile::read_from_gxt(&mut file, &Some(data_ordering), &custom_table, &name_list).expect("Unable to decompile GXT file")
+            GXTFile::read_from_gxt(&mut file, &Some(data_ordering), &custom_table, &name_list)?
         } else {
-            let _f = File::open(&input_filename).expect("Unable to open text file");
-            let mut file = BufReader::new(_f);
+            let mut file = input::open_input(&input_filename)?;
 
-            GXTFile::read_from_text(&mut file).expect("Unable to decompile GXT file")
+            GXTFile::read_from_text(&mut file)?
         };
 
         for (k,v) in gxt.main_table {
@@ -114,20 +167,23 @@ fn main() -> Result<(), gxter::GXTError> {
 
     if decompile {
 
-        let _f = File::open(&input_filename).expect("Unable to open GXT file");
-        let mut file = BufReader::new(_f);
+        let mut file = input::open_input(&input_filename)?;
 
-        let gxt = GXTFile::read_from_gxt(&mut file, &Some(data_ordering), &custom_table, &name_list).expect("Unable to decompile GXT file");
+        let gxt = if matches.opt_present("strict") {
+            GXTFile::read_from_gxt_strict(&mut file, &Some(data_ordering), &custom_table, &name_list)?
+        } else {
+            GXTFile::read_from_gxt(&mut file, &Some(data_ordering), &custom_table, &name_list)?
+        };
         
         let output = matches.opt_str("o");
         match output {
             Some(ofn) => {
-                let mut outfile = File::create(ofn).expect("Unable to open output file");
-                gxt.write_to_text(&mut outfile).unwrap();
+                let mut outfile = compile::create_output(&ofn)?;
+                gxt.write_to_text(&mut outfile)?;
             },
             None => {
                 let mut stdout = io::stdout();
-                gxt.write_to_text(&mut stdout).unwrap();
+                gxt.write_to_text(&mut stdout)?;
             }
         };
         Ok(())
@@ -138,16 +194,18 @@ fn main() -> Result<(), gxter::GXTError> {
 
         match output {
             Some(ofn) => {
-                let _f = File::open(&input_filename).expect("Unable to open text file");
-                let mut file = BufReader::new(_f);
+                let mut file = input::open_input(&input_filename)?;
+
+                let gxt = GXTFile::read_from_text(&mut file)?;
 
-                let gxt = GXTFile::read_from_text(&mut file).expect("Unable to decompile GXT file");
+                let options = compile::write_options(&matches, &custom_table)?;
 
-                let mut outfile = File::create(ofn).expect("Unable to open output file");
-                gxt.write_to_gxt(&mut outfile, &custom_table).unwrap();
+                if !compile::compile(&gxt, &options, matches.opt_present("shadowed"), &ofn)? {
+                    std::process::exit(1);
+                }
             },
             None => {
-                eprintln!("No output file name specified!");
+                eprintln!("No output file name specified! (Use -o - to write to standard output.)");
             },
         }
         Ok(())
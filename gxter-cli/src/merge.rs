@@ -0,0 +1,45 @@
+use crate::compile;
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use gxter::merge::{MergeGranularity, MergePolicy, MergeStrategy};
+use getopts::Matches;
+use std::collections::HashMap;
+
+/// Merges every file given as an argument after the first one into the first one, in order, and
+/// writes the result into the file specified by `-o`: a GXT file, or a text file when decompiling.
+/// Every string or table added or replaced is listed.
+pub fn merge(args: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let Some((base_filename, other_filenames)) = args.split_first().filter(|(_,o)| !o.is_empty()) else {
+        eprintln!("The merge mode requires a base file and at least one file to merge into it!");
+        return Ok(());
+    };
+
+    let granularity = match matches.opt_present("whole-tables") {
+        true => MergeGranularity::Table,
+        false => MergeGranularity::Key,
+    };
+    let strategy = || match matches.opt_str("conflicts").as_deref() {
+        None | Some("theirs") => MergeStrategy::Theirs,
+        Some("ours") => MergeStrategy::Ours,
+        Some("fail") => MergeStrategy::Fail,
+        Some(x) => {
+            eprintln!("Unknown conflict resolution: {x}");
+            std::process::exit(1);
+        },
+    };
+
+    let mut gxt = read_gxt_or_text(base_filename, ordering, custom_table, name_list)?;
+
+    for filename in other_filenames {
+        let other = read_gxt_or_text(filename, ordering, custom_table, name_list)?;
+        let report = gxt.merge(&other, MergePolicy { strategy: strategy(), granularity })?;
+
+        for c in &report.changes {
+            eprintln!("{filename}: {c}");
+        }
+    }
+
+    compile::write_result(&gxt, matches, custom_table)?;
+    Ok(())
+}
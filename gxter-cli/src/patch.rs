@@ -0,0 +1,56 @@
+use crate::compile;
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use gxter::patch::GXTPatch;
+use getopts::Matches;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+
+/// Makes a patch from the differences between two GXT or text files, and writes it on the screen
+/// or into the file specified by `-o`.
+pub fn make_patch(args: &[String], output: Option<String>, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let [old_filename, new_filename] = args else {
+        eprintln!("The make-patch mode requires an old file and a new file!");
+        return Ok(());
+    };
+
+    let old = read_gxt_or_text(old_filename, ordering, custom_table, name_list)?;
+    let new = read_gxt_or_text(new_filename, ordering, custom_table, name_list)?;
+
+    let patch = GXTPatch::from_diff(&gxter::diff::diff(&old, &new, name_list));
+
+    match output {
+        Some(ofn) => {
+            let mut outfile = compile::create_output(&ofn)?;
+            patch.write_to_text(&mut outfile)?;
+        },
+        None => {
+            patch.write_to_text(&mut io::stdout())?;
+        },
+    }
+    Ok(())
+}
+
+/// Applies one or more patches onto a GXT or text file, in order, and writes the result into the
+/// file specified by `-o`: a GXT file, or a text file when decompiling.
+pub fn apply_patch(args: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let Some((base_filename, patch_filenames)) = args.split_first().filter(|(_,p)| !p.is_empty()) else {
+        eprintln!("The apply-patch mode requires a base file and at least one patch!");
+        return Ok(());
+    };
+
+    let mut gxt = read_gxt_or_text(base_filename, ordering, custom_table, name_list)?;
+
+    for filename in patch_filenames {
+        let _f = File::open(filename).expect("Unable to open patch file");
+        let mut file = BufReader::new(_f);
+
+        let patch = gxter::patch::read_patch(&mut file)?;
+        gxt.apply_patch(&patch)?;
+    }
+
+    compile::write_result(&gxt, matches, custom_table)
+}
@@ -1,48 +1,10 @@
 use gxter::GXTFileFormat;
-use std::default::Default;
+use gxter::tags::{GXTButton, GXTToken, tokenize};
 use owo_colors::{OwoColorize, Style, colors::*};
 
-enum GXTToken {
-    Text(String),
-    Tag(String),
-}
-
-fn split_into_tokens(string: &str) -> Result<Vec<GXTToken>,String> {
-
-    let mut res: Vec<GXTToken> = vec!();
-
-    let mut current_token: String = Default::default();
-
-    for e in string.chars().into_iter() {
-        if current_token.len() == 0 {
-            current_token.push(e); // we can't be choosy over
-        } else {
-            if current_token.chars().nth(0) == Some('~') {
-                // we are currently IN a token
-                if e == '~' {
-                    res.push(GXTToken::Tag(current_token[1..].to_string()));
-                    current_token = "".to_string();
-                } else {
-                    current_token.push(e);
-                }
-            } else {
-                // we are currently NOT in a token
-                if e == '~' {
-                    res.push(GXTToken::Text(current_token.to_string()));
-                    current_token = "~".to_string();
-                } else {
-                    current_token.push(e);
-                }
-            }
-        }
-    }
-    res.push(GXTToken::Text(current_token.to_string()));
-    return Ok(res);
-}
-
 pub fn pretty_print(name: &str, string: &str, format: &GXTFileFormat) -> Result<(),String> {
 
-    let tokens = split_into_tokens(&string)?;
+    let tokens = tokenize(string, format);
     let default_style = Style::new().white();
     let mut style = default_style;
 
@@ -53,11 +15,11 @@ pub fn pretty_print(name: &str, string: &str, format: &GXTFileFormat) -> Result<
             GXTToken::Text(s) => {
                 print!("{}",s.style(style));
             },
-            GXTToken::Tag(t) => {
+            GXTToken::Color { tag, .. } => {
 
                 match format {
                     GXTFileFormat::Three => {
-                        match t.as_str() {
+                        match tag.as_str() {
                             "b" => { style = style.fg::<BrightBlue>(); } ,
                             "g" => { style = style.fg::<Green>(); } ,
                             "h" => { style = style.fg::<BrightWhite>(); } ,
@@ -65,12 +27,11 @@ pub fn pretty_print(name: &str, string: &str, format: &GXTFileFormat) -> Result<
                             "r" => { style = style.fg::<Red>(); } ,
                             "w" => { style = style.remove_fg(); } ,
                             "y" => { style = style.fg::<Yellow>(); } ,
-                            _ => { let full_string = "~".to_owned() + &t + "~";
-                                print!("{}",full_string.style(style)); },
+                            _ => {},
                         }
                     },
                     GXTFileFormat::Vice => {
-                        match t.as_str() {
+                        match tag.as_str() {
                             "b" => { style = style.fg::<Blue>(); } ,
                             "g" => { style = style.fg::<BrightRed>(); } ,
                             "h" => { style = style.fg::<BrightWhite>(); } ,
@@ -82,49 +43,81 @@ pub fn pretty_print(name: &str, string: &str, format: &GXTFileFormat) -> Result<
                             "w" => { style = style.fg::<White>(); } ,
                             "x" => { style = style.fg::<BrightBlue>(); } ,
                             "y" => { style = style.fg::<BrightYellow>(); } ,
-                            _ => { let full_string = "~".to_owned() + &t + "~";
-                                print!("{}",full_string.style(style)); },
+                            _ => {},
                         }
                     },
                     GXTFileFormat::San8 | GXTFileFormat::San16 => {
-                        match t.as_str() {
-                            "A" => { print!("{{left analog stick click}}"); } ,
+                        match tag.as_str() {
                             "b" => { style = style.fg::<Blue>(); } ,
-                            "K" => { print!("{{left trigger}}"); } ,
-                            "c" => { print!("{{right analog stick click}}"); } ,
-                            "d" => { print!("{{down on d-pad}}"); } ,
                             "g" => { style = style.fg::<Green>(); } ,
                             "h" => { style = style.fg::<BrightWhite>(); } ,
-                            "j" => { print!("{{right trigger}}"); } ,
                             "l" => { style = style.fg::<Black>(); } ,
-                            "m" => { print!("{{left bumper / white button}}"); } ,
-                                    //cycle weapons left, look left in vehicle, zoom in
-                            "n" => { print!("\n\t"); }, //new line
-                            "o" => { print!("{{right face button}}"); } ,
                             "p" => { style = style.fg::<Magenta>(); } ,
-                            "q" => { print!("{{left face button}}"); } ,
                             "r" => { style = style.fg::<Red>(); } ,
                             "s" => { style = default_style; } ,
-                            "t" => { print!("{{top face button}}"); } ,
-                            "u" => { print!("{{up on d-pad}}"); } ,
-                            "v" => { print!("{{right bumper / black button}}"); } ,
-                                    //cycle weapons right, look right in vehicle, zoom out
                             "w" => { style = style.fg::<White>(); } ,
-                            "x" => { print!("{{bottom face button}}"); } ,
                             "y" => { style = style.fg::<Yellow>(); } ,
-                            "z" => { print!("💬"); } , //subtitle, will be hidden if subtitles are
-                                                       //disabled in the game's options
-                            "<" => { print!("{{left on d-pad}}"); } ,
-                            ">" => { print!("{{right on d-pad}}"); } ,
-                            _ => { let full_string = "~".to_owned() + &t + "~";
-                                print!("{}",full_string.style(style)); },
+                            _ => {},
                         }
                     },
                 }
             },
+            GXTToken::Button { button, .. } => {
+                match button {
+                    GXTButton::LeftStickClick => { print!("{{left analog stick click}}"); } ,
+                    GXTButton::LeftTrigger => { print!("{{left trigger}}"); } ,
+                    GXTButton::RightStickClick => { print!("{{right analog stick click}}"); } ,
+                    GXTButton::DPadDown => { print!("{{down on d-pad}}"); } ,
+                    GXTButton::RightTrigger => { print!("{{right trigger}}"); } ,
+                    GXTButton::LeftBumper => { print!("{{left bumper / white button}}"); } ,
+                            //cycle weapons left, look left in vehicle, zoom in
+                    GXTButton::FaceRight => { print!("{{right face button}}"); } ,
+                    GXTButton::FaceLeft => { print!("{{left face button}}"); } ,
+                    GXTButton::FaceTop => { print!("{{top face button}}"); } ,
+                    GXTButton::DPadUp => { print!("{{up on d-pad}}"); } ,
+                    GXTButton::RightBumper => { print!("{{right bumper / black button}}"); } ,
+                            //cycle weapons right, look right in vehicle, zoom out
+                    GXTButton::FaceBottom => { print!("{{bottom face button}}"); } ,
+                    GXTButton::DPadLeft => { print!("{{left on d-pad}}"); } ,
+                    GXTButton::DPadRight => { print!("{{right on d-pad}}"); } ,
+                }
+            },
+            GXTToken::NewLine => { print!("\n\t"); }, //new line
+            GXTToken::Subtitle => { print!("💬"); } , //subtitle, will be hidden if subtitles are
+                                                      //disabled in the game's options
+            t => {
+                print!("{}",t.to_string().style(style));
+            },
         }
     }
     println!();
     
     return Ok(());
 }
+
+/// Prints the differences between two files, with added strings in green, removed ones in red and
+/// changed ones in yellow.
+pub fn print_diff(diff: &gxter::diff::GXTDiff) {
+
+    use gxter::diff::GXTTableStatus;
+
+    for t in &diff.tables {
+        let header = match &t.status {
+            GXTTableStatus::Added => format!("[{}] (added)", t.table),
+            GXTTableStatus::Removed => format!("[{}] (removed)", t.table),
+            GXTTableStatus::Renamed { from } => format!("[{}] (renamed from {})", t.table, from),
+            GXTTableStatus::Changed => format!("[{}]", t.table),
+        };
+        println!("{}", header.bold());
+
+        for (k,v) in &t.added {
+            println!("{}", format!("+ {k} = {v:?}").green());
+        }
+        for (k,v) in &t.removed {
+            println!("{}", format!("- {k} = {v:?}").red());
+        }
+        for (k,v) in &t.changed {
+            println!("{} = {} -> {}", format!("~ {k}").yellow(), format!("{:?}", v.old).red(), format!("{:?}", v.new).green());
+        }
+    }
+}
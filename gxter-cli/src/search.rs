@@ -0,0 +1,77 @@
+use crate::compile;
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use gxter::search::GXTSearch;
+use getopts::Matches;
+use regex::Regex;
+use std::collections::HashMap;
+
+// builds the search from a pattern and the --keys and --table options. exits if the pattern isn't
+// a valid regular expression
+fn search_from(pattern: &str, matches: &Matches) -> GXTSearch {
+
+    let regex = match Regex::new(pattern) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Invalid pattern: {e}");
+            std::process::exit(1);
+        },
+    };
+
+    let (key, value) = match matches.opt_present("keys") {
+        true => (Some(regex), None),
+        false => (None, Some(regex)),
+    };
+    GXTSearch { key, value, tables: matches.opt_strs("table") }
+}
+
+/// Prints every string in the given GXT or text files whose value (or name, with `--keys`) matches
+/// a regular expression. Exits with a non-zero status if nothing was found, like `grep` does.
+pub fn grep(args: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let Some((pattern, filenames)) = args.split_first().filter(|(_,f)| !f.is_empty()) else {
+        eprintln!("The grep mode requires a pattern and at least one GXT or text file!");
+        return Ok(());
+    };
+
+    let search = search_from(pattern, matches);
+    let mut found_count: usize = 0;
+
+    for filename in filenames {
+        let gxt = read_gxt_or_text(filename, ordering, custom_table, name_list)?;
+
+        for m in gxt.search(&search) {
+            println!("{filename}: {m}");
+            found_count += 1;
+        }
+    }
+
+    if found_count == 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Replaces every match of a regular expression in the strings of a GXT or text file, leaving
+/// tags alone unless `--include-tags` is given, and writes the result into the file specified by
+/// `-o`: a GXT file, or a text file when decompiling. Every changed string is listed.
+pub fn replace(args: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let [pattern, replacement, filename] = args else {
+        eprintln!("The replace mode requires a pattern, a replacement and a GXT or text file!");
+        return Ok(());
+    };
+
+    if matches.opt_present("keys") {
+        eprintln!("The replace mode only replaces text in string values!");
+        return Ok(());
+    }
+
+    let mut gxt = read_gxt_or_text(filename, ordering, custom_table, name_list)?;
+
+    for r in gxt.replace(&search_from(pattern, matches), replacement, !matches.opt_present("include-tags")) {
+        eprintln!("{r}");
+    }
+
+    compile::write_result(&gxt, matches, custom_table)
+}
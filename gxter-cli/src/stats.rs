@@ -0,0 +1,53 @@
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use std::collections::HashMap;
+
+/// Prints the number of strings, words and characters in every table of a GXT or text file, along
+/// with how often each character occurs. If a source file is given as well, also prints how many
+/// strings are identical to the source or missing from the file.
+pub fn stats(args: &[String], output_format: &Option<String>, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let (filename, source_filename) = match args {
+        [f] => (f, None),
+        [f, s] => (f, Some(s)),
+        _ => {
+            eprintln!("The stats mode requires a GXT or text file, and optionally its source file!");
+            return Ok(());
+        },
+    };
+
+    // the custom table only applies to the file itself, the source is expected to be a stock file
+    let gxt = read_gxt_or_text(filename, ordering, custom_table, name_list)?;
+    let source = match source_filename {
+        Some(s) => Some(read_gxt_or_text(s, ordering, &None, name_list)?),
+        None => None,
+    };
+
+    let stats = gxter::stats::stats(&gxt, source.as_ref());
+    let total = stats.total();
+
+    match output_format.as_deref() {
+        None | Some("text") => {
+            for t in stats.tables.iter().chain(std::iter::once(&total)) {
+                println!("{t}");
+
+                // the most frequent characters first
+                let mut frequency: Vec<(&char, &usize)> = t.character_frequency.iter().collect();
+                frequency.sort_by(|a,b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+                if !frequency.is_empty() {
+                    let list: Vec<String> = frequency.iter().map(|(c, n)| format!("{c:?} {n}")).collect();
+                    println!("    {}", list.join(", "));
+                }
+            }
+        },
+        Some("json") => {
+            let value = serde_json::json!({ "tables": stats.tables, "total": total });
+            println!("{}", serde_json::to_string_pretty(&value).expect("Unable to serialize the statistics"));
+        },
+        Some(x) => {
+            eprintln!("Unknown output format: {x}");
+            std::process::exit(1);
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use crate::compile;
+use crate::input::read_gxt_or_text;
+use gxter::{GXTCharacterTable, ImportOrdering};
+use gxter::merge::{MergePolicy, MergeStrategy};
+use getopts::Matches;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+// editors often save a file in several steps, so changes are only acted on once they stop
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+// reads the source files (and the character table) again and compiles them, returning false if
+// there were any problems
+fn rebuild(sources: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, name_list: &Option<HashMap<u32,String>>, output_filename: &str) -> Result<bool, gxter::GXTError> {
+
+    // a file being saved may briefly not exist at all
+    if let Some(f) = sources.iter().chain(matches.opt_str("character-table").iter()).find(|f| !Path::new(f).is_file()) {
+        eprintln!("{f}: file not found");
+        return Ok(false);
+    }
+
+    let custom_table: Option<GXTCharacterTable> = match matches.opt_str("character-table") {
+        Some(name) => {
+            let _f = File::open(&name)?;
+            let mut file = BufReader::new(_f);
+
+            Some(gxter::read_custom_table(&mut file)?)
+        },
+        None => None,
+    };
+
+    let mut gxt = read_gxt_or_text(&sources[0], ordering, &custom_table, name_list)?;
+    for filename in &sources[1..] {
+        let other = read_gxt_or_text(filename, ordering, &custom_table, name_list)?;
+        gxt.merge(&other, MergePolicy::new(MergeStrategy::Theirs))?;
+    }
+
+    let options = compile::write_options(matches, &custom_table)?;
+    compile::compile(&gxt, &options, matches.opt_present("shadowed"), output_filename)
+}
+
+// the full path of a file, as notify reports it when the file's directory is watched
+fn watched_path(filename: &str) -> Result<PathBuf, gxter::GXTError> {
+    let path = Path::new(filename);
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    Ok(dir.canonicalize()?.join(path.file_name().unwrap_or_default()))
+}
+
+/// Compiles the source files into the file specified by `-o`, then does it again whenever one of
+/// them (or the custom character table) changes. If there are several source files, the later
+/// ones are merged into the first one. Problems are printed, but don't stop the program.
+pub fn watch(args: &[String], matches: &Matches, ordering: &Option<ImportOrdering>, name_list: &Option<HashMap<u32,String>>) -> Result<(), gxter::GXTError> {
+
+    let Some(output_filename) = matches.opt_str("output") else {
+        eprintln!("The watch mode requires an output file name!");
+        return Ok(());
+    };
+    if args.is_empty() {
+        eprintln!("The watch mode requires at least one text file!");
+        return Ok(());
+    }
+
+    // directories are watched rather than the files themselves, since editors often save a file
+    // by replacing it with a new one
+    let files: Vec<String> = args.iter().cloned().chain(matches.opt_str("character-table")).collect();
+    let paths: HashSet<PathBuf> = files.iter().map(|f| watched_path(f)).collect::<Result<_,_>>()?;
+    let dirs: HashSet<&Path> = paths.iter().filter_map(|p| p.parent()).collect();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).unwrap_or_else(|e| {
+        eprintln!("Unable to watch for file changes: {e}");
+        std::process::exit(1);
+    });
+    for dir in dirs {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            eprintln!("Unable to watch {}: {e}", dir.display());
+            std::process::exit(1);
+        }
+    }
+
+    // only changes to the watched files count, not reading them (or writing the output)
+    let is_relevant = |event: &notify::Result<notify::Event>| match event {
+        Ok(e) => matches!(e.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+            && e.paths.iter().any(|p| paths.contains(p)),
+        Err(_) => false,
+    };
+
+    loop {
+        match rebuild(args, matches, ordering, name_list, &output_filename) {
+            Ok(true) => { eprintln!("Compiled {output_filename}, waiting for changes..."); },
+            Ok(false) => { eprintln!("Waiting for changes..."); },
+            Err(e) => { eprintln!("{e}\nWaiting for changes..."); },
+        }
+
+        loop {
+            match rx.recv() {
+                Ok(event) if is_relevant(&event) => break,
+                Ok(_) => {},
+                Err(_) => return Ok(()), // the watcher is gone
+            }
+        }
+        while rx.recv_timeout(SETTLE_TIME).is_ok() {}
+    }
+}
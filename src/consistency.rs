@@ -0,0 +1,185 @@
+//! This module is used to check that a translated GXT file keeps the placeholders and tags of the
+//! file it was translated from.
+//!
+//! A translation that drops a `~1~` placeholder or a button tag may crash the game or show
+//! broken mission text. [check_translation] compares two [GXTFile]s table by table and key by
+//! key, and reports every string whose placeholders and non-color tags differ, as well as strings
+//! and tables that only exist in one of the files. Color tags are not compared, as translations
+//! often need to highlight different words.
+//!
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::{GXTFile, GXTFileFormat};
+use crate::tags::{GXTToken, tokenize};
+
+/// Describes how a translation differs from its source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GXTConsistencyKind {
+    /// The table exists in the source, but not in the translation.
+    MissingTable,
+    /// The table exists in the translation, but not in the source.
+    ExtraTable,
+    /// The string exists in the source, but not in the translation.
+    MissingKey,
+    /// The string exists in the translation, but not in the source.
+    ExtraKey,
+    /// The string's placeholders and non-color tags don't match.
+    TagMismatch {
+        /// Tags that the source string has more of than the translation.
+        missing: Vec<String>,
+        /// Tags that the translation has more of than the source string.
+        extra: Vec<String>,
+    },
+}
+
+/// Describes a single difference between a source file and its translation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTConsistencyIssue {
+    /// The name of the table (the main table is called [crate::MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The name of the string, or None if the issue concerns the whole table.
+    pub key: Option<String>,
+    /// The difference itself.
+    pub kind: GXTConsistencyKind,
+}
+
+impl fmt::Display for GXTConsistencyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(k) => write!(f, "[{}] {}: ", self.table, k)?,
+            None => write!(f, "[{}]: ", self.table)?,
+        }
+        match &self.kind {
+            GXTConsistencyKind::MissingTable => write!(f, "table is missing from the translation"),
+            GXTConsistencyKind::ExtraTable => write!(f, "table does not exist in the source"),
+            GXTConsistencyKind::MissingKey => write!(f, "string is missing from the translation"),
+            GXTConsistencyKind::ExtraKey => write!(f, "string does not exist in the source"),
+            GXTConsistencyKind::TagMismatch { missing, extra } => {
+                write!(f, "tags do not match")?;
+                if !missing.is_empty() { write!(f, ", missing {}", missing.join(" "))?; }
+                if !extra.is_empty() { write!(f, ", extra {}", extra.join(" "))?; }
+                Ok(())
+            },
+        }
+    }
+}
+
+// counts every placeholder and non-color tag in the string
+fn tag_multiset(string: &str, format: &GXTFileFormat) -> BTreeMap<String, usize> {
+
+    let mut res: BTreeMap<String, usize> = BTreeMap::new();
+
+    for t in tokenize(string, format) {
+        match t {
+            GXTToken::Text(_) | GXTToken::Color { .. } => {},
+            t => { *res.entry(t.to_string()).or_default() += 1; },
+        }
+    }
+
+    res
+}
+
+/// Compares a translated file against its source, returning every difference in placeholders and
+/// non-color tags, along with strings and tables that exist in only one of the files. Each file's
+/// tags are interpreted according to its own format.
+///
+/// An empty result means the translation is consistent with the source.
+pub fn check_translation(source: &GXTFile, translation: &GXTFile) -> Vec<GXTConsistencyIssue> {
+
+    let mut res: Vec<GXTConsistencyIssue> = vec!();
+    let translation_tables: BTreeMap<&str, _> = translation.tables().collect();
+
+    for (table, source_strings) in source.tables() {
+
+        let Some(translated_strings) = translation_tables.get(table) else {
+            res.push(GXTConsistencyIssue { table: table.to_string(), key: None, kind: GXTConsistencyKind::MissingTable });
+            continue;
+        };
+
+        for (key, source_string) in source_strings {
+
+            let Some(translated_string) = translated_strings.get(key) else {
+                res.push(GXTConsistencyIssue { table: table.to_string(), key: Some(key.clone()), kind: GXTConsistencyKind::MissingKey });
+                continue;
+            };
+
+            let source_tags = tag_multiset(source_string, &source.format);
+            let translated_tags = tag_multiset(translated_string, &translation.format);
+
+            if source_tags != translated_tags {
+                let difference = |a: &BTreeMap<String,usize>, b: &BTreeMap<String,usize>| -> Vec<String> {
+                    a.iter().flat_map(|(tag, count)| {
+                        let surplus = count.saturating_sub(*b.get(tag).unwrap_or(&0));
+                        std::iter::repeat_n(tag.clone(), surplus)
+                    }).collect()
+                };
+
+                res.push(GXTConsistencyIssue {
+                    table: table.to_string(),
+                    key: Some(key.clone()),
+                    kind: GXTConsistencyKind::TagMismatch {
+                        missing: difference(&source_tags, &translated_tags),
+                        extra: difference(&translated_tags, &source_tags),
+                    },
+                });
+            }
+        }
+
+        for key in translated_strings.keys().filter(|k| !source_strings.contains_key(*k)) {
+            res.push(GXTConsistencyIssue { table: table.to_string(), key: Some(key.clone()), kind: GXTConsistencyKind::ExtraKey });
+        }
+    }
+
+    let source_tables: BTreeMap<&str, _> = source.tables().collect();
+    for (table, _) in translation.tables().filter(|(t,_)| !source_tables.contains_key(t)) {
+        res.push(GXTConsistencyIssue { table: table.to_string(), key: None, kind: GXTConsistencyKind::ExtraTable });
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+    use super::*;
+
+    #[test]
+    fn check_translation_test() {
+
+        let source = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("CASH".to_string(),"You earned ~r~$~1~~s~! Press ~k~~PED_FIREWEAPON~.".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+                ("TWO".to_string(),"~1~ of ~1~".to_string()),
+            ]),
+            IndexMap::from([("AUX1".to_string(), IndexMap::new())]),
+            );
+
+        let translation = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("CASH".to_string(),"Du hast ~g~$~1~~s~ verdient! ~k~~PED_FIREWEAPON~ drücken.".to_string()),
+                ("TWO".to_string(),"~1~ von ~n~".to_string()),
+                ("NEW".to_string(),"Neu".to_string()),
+            ]),
+            IndexMap::from([("AUX2".to_string(), IndexMap::new())]),
+            );
+
+        let issues = check_translation(&source, &translation);
+
+        // color tags are allowed to differ
+        assert!( issues == vec!(
+            GXTConsistencyIssue { table: "MAIN".to_string(), key: Some("BYE".to_string()), kind: GXTConsistencyKind::MissingKey },
+            GXTConsistencyIssue { table: "MAIN".to_string(), key: Some("TWO".to_string()), kind: GXTConsistencyKind::TagMismatch {
+                missing: vec!("~1~".to_string()),
+                extra: vec!("~n~".to_string()),
+            }},
+            GXTConsistencyIssue { table: "MAIN".to_string(), key: Some("NEW".to_string()), kind: GXTConsistencyKind::ExtraKey },
+            GXTConsistencyIssue { table: "AUX1".to_string(), key: None, kind: GXTConsistencyKind::MissingTable },
+            GXTConsistencyIssue { table: "AUX2".to_string(), key: None, kind: GXTConsistencyKind::ExtraTable },
+        ));
+
+        assert!( check_translation(&source, &source).is_empty() );
+    }
+}
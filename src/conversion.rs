@@ -0,0 +1,507 @@
+//! This module is used to port GXT strings between games, whose tags mean different things.
+//!
+//! Vice City's orange `~o~` is a button icon in San Andreas, and its green is `~t~` rather than
+//! `~g~` (which is pink there). [GXTTagMapping] describes how to rewrite the tags of one format
+//! into another, and [GXTFile::convert_tags] applies it to every string in a file, reporting the
+//! tags that have no equivalent as a list of [GXTUnmappedTag]s. Such tags are removed, since they
+//! would mean something else in the target format, unless the mapping says to keep them.
+//!
+//! [GXTTagMapping::between] builds a mapping for a pair of formats. Colors that don't exist in the
+//! target format are replaced with the closest one that does (for example, Vice City's light blue
+//! becomes blue in San Andreas), while button icons and subtitle markers are only mapped if the
+//! target format has them. Mappings can also be loaded from a TOML file with [read_tag_mapping],
+//! which lists the tags' contents without the tildes:
+//!
+//! ```toml
+//! [tags]
+//! o = "y"     # orange becomes yellow
+//! x = "b"     # light blue becomes blue
+//! z = ""      # subtitle markers are removed
+//! ```
+//!
+//! Adding `keep_unmapped = true` at the top of the file keeps tags that have no entry instead of
+//! removing them.
+//!
+//! [GXTFile::convert_to] goes further and converts a whole file to another format: besides the
+//! tags, it converts string names between plain names and CRC32 hashes, re-encodes every string
+//! with the target format's character table and deals with auxiliary tables when converting to
+//! GTA III. Everything that couldn't be carried over exactly is listed in a
+//! [GXTConversionReport].
+//!
+use std::collections::HashMap;
+use std::fmt;
+use std::io::prelude::*;
+use indexmap::IndexMap;
+use crate::{GXTError, GXTFile, GXTFileFormat, GXTSubstitution, WriteOptions, MAIN_TABLE_NAME};
+use crate::{decode_string, encode_string, parse_toml, string_to_name_basic, string_to_name_crc32};
+use crate::tags::{GXTColor, GXTToken, tag_button, tag_color, tokenize};
+
+// the contents of every color and button tag known for any format, as well as the subtitle marker
+const TAG_CANDIDATES: &[&str] = &[
+    "b", "g", "h", "l", "o", "p", "r", "s", "t", "w", "x", "y",
+    "A", "c", "K", "j", "m", "v", "q", "u", "d", "<", ">", "z",
+];
+
+/// Describes how to rewrite the tags of one format into the tags of another.
+#[derive(serde::Serialize,serde::Deserialize,Clone,Debug,Default)]
+pub struct GXTTagMapping {
+    /// Maps the contents of a tag (without the tildes) to the contents of the tag that replaces
+    /// it. An empty string removes the tag altogether.
+    ///
+    /// Line breaks and number placeholders work the same in every format, so they are kept as they
+    /// are unless they have an entry here. Key bindings are always kept. Every other tag without
+    /// an entry is reported as having no equivalent, and removed unless `keep_unmapped` is set.
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+
+    /// Keep tags that have no entry in `tags` as they are, instead of removing them. Their meaning
+    /// may be different in the target format: Vice City's orange `~o~` is a button in San Andreas.
+    #[serde(default)]
+    pub keep_unmapped: bool,
+}
+
+/// Describes a tag that could not be converted, along with its location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTUnmappedTag {
+    /// The name of the table (the main table is called [crate::MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The name of the string.
+    pub key: String,
+    /// The position of the tag in the original string, counted in characters, not bytes.
+    pub position: usize,
+    /// The tag's contents, without the tildes.
+    pub tag: String,
+}
+
+impl fmt::Display for GXTUnmappedTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}, position {}: tag ~{}~ has no equivalent", self.table, self.key, self.position, self.tag)
+    }
+}
+
+// colors to use in place of a color, in order of preference
+fn closest_colors(color: GXTColor) -> Vec<GXTColor> {
+    match color {
+        GXTColor::Default => vec!(GXTColor::Default, GXTColor::White),
+        GXTColor::White => vec!(GXTColor::White, GXTColor::Default),
+        GXTColor::Pink => vec!(GXTColor::Pink, GXTColor::Red),
+        GXTColor::Orange => vec!(GXTColor::Orange, GXTColor::Yellow),
+        GXTColor::LightBlue => vec!(GXTColor::LightBlue, GXTColor::Blue),
+        GXTColor::Purple => vec!(GXTColor::Purple, GXTColor::Pink, GXTColor::Blue),
+        c => vec!(c),
+    }
+}
+
+impl GXTTagMapping {
+    /// Builds the default mapping for converting strings from one format to another.
+    ///
+    /// Colors are mapped to the same color in the target format, or to the closest one if it
+    /// doesn't exist there. Button icons and subtitle markers are only mapped if the target format
+    /// has them as well.
+    pub fn between(from: &GXTFileFormat, to: &GXTFileFormat) -> Self {
+
+        let find_tag = |f: &dyn Fn(&str) -> bool| TAG_CANDIDATES.iter().find(|t| f(t)).map(|t| t.to_string());
+        let mut tags: HashMap<String, String> = HashMap::new();
+
+        for tag in TAG_CANDIDATES {
+            let target = if let Some(color) = tag_color(tag, from) {
+                closest_colors(color).into_iter()
+                    .find_map(|c| find_tag(&|t| tag_color(t, to) == Some(c)))
+            } else if let Some(button) = tag_button(tag, from) {
+                find_tag(&|t| tag_button(t, to) == Some(button))
+            } else if *tag == "z" && matches!(from, GXTFileFormat::San8 | GXTFileFormat::San16)
+                    && matches!(to, GXTFileFormat::San8 | GXTFileFormat::San16) {
+                Some("z".to_string())
+            } else {
+                None
+            };
+
+            if let Some(t) = target {
+                tags.insert(tag.to_string(), t);
+            }
+        }
+
+        GXTTagMapping { tags, keep_unmapped: false }
+    }
+}
+
+/// Read a tag mapping from a TOML file. See the [module documentation](self) for the format.
+pub fn read_tag_mapping(file: &mut (impl Read + std::io::Seek)) -> Result<GXTTagMapping,GXTError> {
+
+    let mut raw_data: String = Default::default();
+    file.read_to_string(&mut raw_data)?;
+
+    let mapping: GXTTagMapping = parse_toml(&raw_data)?;
+    Ok(mapping)
+}
+
+/// Rewrites the tags of a single string, interpreting them according to the `from` format.
+/// Returns the converted string, along with the tags that have no equivalent and their positions
+/// in the original string (counted in characters). Those tags are removed, unless the mapping
+/// says to keep them.
+///
+/// Malformed tags (empty or unclosed ones) are kept as they are and not reported.
+pub fn convert_string_tags(string: &str, from: &GXTFileFormat, mapping: &GXTTagMapping) -> (String, Vec<(usize, String)>) {
+
+    let mut res = String::new();
+    let mut unmapped: Vec<(usize, String)> = vec!();
+    let mut position: usize = 0;
+
+    for t in tokenize(string, from) {
+        let original = t.to_string();
+        let contents = match &t {
+            GXTToken::Color { tag, .. } | GXTToken::Button { tag, .. } => Some(tag.clone()),
+            GXTToken::Unknown(tag) if !tag.is_empty() => Some(tag.clone()),
+            GXTToken::Subtitle => Some("z".to_string()),
+            GXTToken::NewLine => Some("n".to_string()),
+            GXTToken::Number(n) => Some(n.clone()),
+            _ => None,
+        };
+
+        match contents.map(|c| (mapping.tags.get(&c), c)) {
+            Some((Some(target), _)) if target.is_empty() => {},
+            Some((Some(target), _)) => { res.push_str(&format!("~{target}~")); },
+            Some((None, _)) if matches!(t, GXTToken::NewLine | GXTToken::Number(_)) => { res.push_str(&original); },
+            Some((None, c)) => {
+                unmapped.push((position, c));
+                if mapping.keep_unmapped {
+                    res.push_str(&original);
+                }
+            },
+            None => { res.push_str(&original); },
+        }
+        position += original.chars().count();
+    }
+
+    (res, unmapped)
+}
+
+impl GXTFile {
+    /// Rewrites the tags of every string in the file using the mapping, interpreting them
+    /// according to the file's current format. Returns the tags that have no equivalent, which are
+    /// removed unless the mapping says to keep them.
+    ///
+    /// The file's format itself is not changed.
+    pub fn convert_tags(&mut self, mapping: &GXTTagMapping) -> Vec<GXTUnmappedTag> {
+
+        let mut res: Vec<GXTUnmappedTag> = vec!();
+        let format = self.format;
+
+        let main = std::iter::once((MAIN_TABLE_NAME.to_string(), &mut self.main_table));
+        let aux = self.aux_tables.iter_mut().map(|(n, t)| (n.clone(), t));
+
+        for (table, strings) in main.chain(aux) {
+            for (key, value) in strings.iter_mut() {
+                let (converted, unmapped) = convert_string_tags(value, &format, mapping);
+                *value = converted;
+                for (position, tag) in unmapped {
+                    res.push(GXTUnmappedTag { table: table.clone(), key: key.clone(), position, tag });
+                }
+            }
+        }
+
+        res
+    }
+}
+
+/// Options that change how a file is converted by [GXTFile::convert_to].
+pub struct GXTConversionOptions<'a> {
+
+    /// Options used to re-encode every string for the target format: its custom character table,
+    /// fallback policy and normalization form.
+    pub write_options: WriteOptions<'a>,
+
+    /// An optional name list, used to turn hashed string names back into plain ones when
+    /// converting from San Andreas to GTA III or Vice City. Hashes that aren't in the list can't be
+    /// converted.
+    pub name_list: &'a Option<HashMap<u32,String>>,
+
+    /// The mapping used to rewrite the strings' tags. If None, the default mapping between the two
+    /// formats is used (see [GXTTagMapping::between]).
+    pub tag_mapping: Option<GXTTagMapping>,
+
+    /// When converting to GTA III, which only has a main table, move the strings of every
+    /// auxiliary table into the main table. If false, converting a file with auxiliary tables to
+    /// GTA III fails.
+    pub merge_aux_tables: bool,
+}
+
+impl Default for GXTConversionOptions<'_> {
+    fn default() -> Self {
+        GXTConversionOptions {
+            write_options: Default::default(),
+            name_list: &None,
+            tag_mapping: None,
+            merge_aux_tables: false,
+        }
+    }
+}
+
+/// Describes why a string was left out of a converted file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GXTDropReason {
+    /// The string's name is a hash that isn't in the name list, so it can't be turned into a
+    /// plain name.
+    UnresolvedHash,
+    /// The string's name can't be used in the target format (for example, it is longer than 8
+    /// bytes).
+    InvalidName,
+    /// The target table already has a string with the same name (or, in San Andreas, the same
+    /// hash). The string that came first is kept.
+    DuplicateKey,
+}
+
+/// Describes a string that was left out of a converted file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTDroppedString {
+    /// The name of the table the string was in (the main table is called [MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The string's name in the original file.
+    pub key: String,
+    /// Why the string was left out.
+    pub reason: GXTDropReason,
+}
+
+impl fmt::Display for GXTDroppedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: ", self.table, self.key)?;
+        match self.reason {
+            GXTDropReason::UnresolvedHash => write!(f, "hash not found in the name list, string dropped"),
+            GXTDropReason::InvalidName => write!(f, "name not valid in the target format, string dropped"),
+            GXTDropReason::DuplicateKey => write!(f, "name already used in the target table, string dropped"),
+        }
+    }
+}
+
+/// Lists everything that [GXTFile::convert_to] could not carry over exactly.
+#[derive(Default, Debug)]
+pub struct GXTConversionReport {
+    /// Characters replaced by the fallback policy, grouped by table name, then by string name,
+    /// same as in [crate::GXTWriteReport].
+    pub substitutions: IndexMap<String, IndexMap<String, Vec<GXTSubstitution>>>,
+    /// Tags with no equivalent in the target format. They are removed, unless the tag mapping
+    /// says to keep them.
+    pub unmapped_tags: Vec<GXTUnmappedTag>,
+    /// Strings left out of the converted file.
+    pub dropped: Vec<GXTDroppedString>,
+    /// Names of the auxiliary tables merged into the main table.
+    pub merged_tables: Vec<String>,
+}
+
+impl GXTConversionReport {
+    /// Returns true if nothing was lost or changed beyond the expected format differences.
+    /// Merged tables are not counted, as the strings themselves are kept.
+    pub fn is_lossless(&self) -> bool {
+        self.substitutions.is_empty() && self.unmapped_tags.is_empty() && self.dropped.is_empty()
+    }
+}
+
+// converts a string name to the target format, or returns the reason why that isn't possible
+fn convert_name(name: &str, to: &GXTFileFormat, name_list: &Option<HashMap<u32,String>>) -> Result<String, GXTDropReason> {
+
+    match to {
+        GXTFileFormat::San8 | GXTFileFormat::San16 => {
+            let hash = string_to_name_crc32(name).map_err(|_| GXTDropReason::InvalidName)?;
+            Ok(format!("#{hash:08X}"))
+        },
+        GXTFileFormat::Three | GXTFileFormat::Vice => {
+            let name = if name.starts_with('#') && !name.starts_with("##") {
+                let hash = string_to_name_crc32(name).map_err(|_| GXTDropReason::InvalidName)?;
+                name_list.as_ref().and_then(|l| l.get(&hash)).ok_or(GXTDropReason::UnresolvedHash)?.clone()
+            } else {
+                name.to_string()
+            };
+            string_to_name_basic(&name).map_err(|_| GXTDropReason::InvalidName)?;
+            Ok(name)
+        },
+    }
+}
+
+// encodes a string in the target format and decodes it back, so that it only contains what the
+// target format can actually store
+fn recode_string(string: &str, to: &GXTFileFormat, options: &WriteOptions, substitutions: &mut Vec<GXTSubstitution>) -> Result<String, GXTError> {
+
+    let bytes = encode_string(string, to, options, substitutions)?;
+    let codes: Vec<u16> = match to {
+        GXTFileFormat::San8 => bytes.iter().map(|b| *b as u16).collect(),
+        _ => bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect(),
+    };
+    // the last code is the null-terminator
+    Ok(decode_string(&codes[..codes.len()-1], to, options.custom_table))
+}
+
+impl GXTFile {
+    /// Converts the file into a different format, returning the converted file along with a
+    /// report of everything that couldn't be carried over exactly.
+    ///
+    /// String names are turned into CRC32 hashes when converting to San Andreas, and hashes are
+    /// turned back into plain names using the name list when converting to GTA III or Vice City.
+    /// Strings whose names can't be converted are left out. Tags are rewritten using the tag
+    /// mapping, and every string is re-encoded with the target format's character table, so
+    /// unsupported characters either get replaced by the fallback policy or make the conversion
+    /// fail.
+    pub fn convert_to(&self, format: GXTFileFormat, options: &GXTConversionOptions) -> Result<(GXTFile, GXTConversionReport), GXTError> {
+
+        let mut report = GXTConversionReport::default();
+        let default_mapping;
+        let mapping = match &options.tag_mapping {
+            Some(m) => m,
+            None => {
+                default_mapping = GXTTagMapping::between(&self.format, &format);
+                &default_mapping
+            },
+        };
+
+        let mut main_table: IndexMap<String,String> = IndexMap::new();
+        let mut aux_tables: IndexMap<String,IndexMap<String,String>> = IndexMap::new();
+
+        for (table, strings) in self.tables() {
+
+            let target = if table == MAIN_TABLE_NAME {
+                &mut main_table
+            } else if let GXTFileFormat::Three = format {
+                if !options.merge_aux_tables {
+                    return Err(GXTError::AuxTablesNotAllowed);
+                }
+                report.merged_tables.push(table.to_string());
+                &mut main_table
+            } else {
+                aux_tables.entry(table.to_string()).or_default()
+            };
+
+            for (key, value) in strings {
+
+                let drop = |reason| GXTDroppedString { table: table.to_string(), key: key.clone(), reason };
+
+                let new_key = match convert_name(key, &format, options.name_list) {
+                    Ok(k) => k,
+                    Err(reason) => { report.dropped.push(drop(reason)); continue; },
+                };
+                if target.contains_key(&new_key) {
+                    report.dropped.push(drop(GXTDropReason::DuplicateKey));
+                    continue;
+                }
+
+                let (value, unmapped) = convert_string_tags(value, &self.format, mapping);
+                for (position, tag) in unmapped {
+                    report.unmapped_tags.push(GXTUnmappedTag { table: table.to_string(), key: key.clone(), position, tag });
+                }
+
+                let mut substitutions: Vec<GXTSubstitution> = vec!();
+                let value = recode_string(&value, &format, &options.write_options, &mut substitutions)?;
+                if !substitutions.is_empty() {
+                    report.substitutions.entry(table.to_string()).or_default().insert(key.clone(), substitutions);
+                }
+
+                target.insert(new_key, value);
+            }
+        }
+
+        Ok((GXTFile::new(format, main_table, aux_tables), report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+    use super::*;
+
+    #[test]
+    fn default_mapping_test() {
+
+        let vc_to_sa = GXTTagMapping::between(&GXTFileFormat::Vice, &GXTFileFormat::San8);
+        assert!( vc_to_sa.tags["t"] == "g" );
+        assert!( vc_to_sa.tags["g"] == "r" );   // there is no pink in San Andreas
+        assert!( vc_to_sa.tags["o"] == "y" );
+        assert!( vc_to_sa.tags["x"] == "b" );
+        assert!( vc_to_sa.tags["w"] == "w" );
+
+        let sa_to_three = GXTTagMapping::between(&GXTFileFormat::San16, &GXTFileFormat::Three);
+        assert!( sa_to_three.tags["s"] == "w" );
+        assert!( sa_to_three.tags["p"] == "b" );
+        assert!( !sa_to_three.tags.contains_key("o") ); // a button
+        assert!( !sa_to_three.tags.contains_key("z") );
+
+        let sa_to_sa = GXTTagMapping::between(&GXTFileFormat::San8, &GXTFileFormat::San16);
+        assert!( sa_to_sa.tags.iter().all(|(k,v)| k == v) );
+    }
+
+    #[test]
+    fn convert_tags_test() {
+
+        let mut x = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([("HELP".to_string(),"~z~Press ~o~ or ~k~~PED_FIREWEAPON~ ~p~~1~~s~ times~n~now".to_string())]),
+            IndexMap::from([("AUX1".to_string(),
+                    IndexMap::from([("ODD".to_string(),"~Q~ and ~r".to_string())]))]),
+            );
+
+        let mut mapping = GXTTagMapping::between(&GXTFileFormat::San8, &GXTFileFormat::Vice);
+        mapping.tags.insert("z".to_string(), "".to_string());
+
+        let unmapped = x.convert_tags(&mapping);
+        // SA's ~o~ is a button, which would be orange text in VC, so it's removed
+        assert!( x.main_table["HELP"] == "Press  or ~k~~PED_FIREWEAPON~ ~p~~1~~w~ times~n~now" );
+        assert!( x.aux_tables["AUX1"]["ODD"] == " and ~r" );
+        assert!( unmapped == vec!(
+            GXTUnmappedTag { table: "MAIN".to_string(), key: "HELP".to_string(), position: 9, tag: "o".to_string() },
+            GXTUnmappedTag { table: "AUX1".to_string(), key: "ODD".to_string(), position: 0, tag: "Q".to_string() },
+        ));
+
+        // unless the mapping says to keep such tags
+        let mut x = GXTFile::new(GXTFileFormat::San8, IndexMap::from([("HELP".to_string(),"Press ~o~".to_string())]), IndexMap::new());
+        mapping.keep_unmapped = true;
+        assert!( x.convert_tags(&mapping).len() == 1 && x.main_table["HELP"] == "Press ~o~" );
+    }
+
+    #[test]
+    fn convert_to_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("HELLO".to_string(),"~t~Ça va?".to_string())]),
+            IndexMap::from([("AUX1".to_string(),
+                    IndexMap::from([("BYE".to_string(),"~o~Bye~w~".to_string())]))]),
+            );
+
+        // to San Andreas: names get hashed, colors get remapped, Ç exists in CP1252
+        let (sa, report) = x.convert_to(GXTFileFormat::San8, &Default::default()).unwrap();
+        assert!( report.is_lossless() );
+        let hello = format!("#{:08X}", string_to_name_crc32("HELLO").unwrap());
+        assert!( sa.main_table[&hello] == "~g~Ça va?" );
+        assert!( sa.aux_tables["AUX1"].values().next().unwrap() == "~y~Bye~w~" );
+
+        // and back: hashes are resolved using the name list, unless they're missing from it
+        let name_list = Some(HashMap::from([(string_to_name_crc32("HELLO").unwrap(), "HELLO".to_string())]));
+        let options = GXTConversionOptions { name_list: &name_list, ..Default::default() };
+        let (vc, report) = sa.convert_to(GXTFileFormat::Vice, &options).unwrap();
+        assert!( vc.main_table["HELLO"] == "~t~Ça va?" );
+        assert!( vc.aux_tables["AUX1"].is_empty() );
+        assert!( report.dropped.len() == 1 && report.dropped[0].reason == GXTDropReason::UnresolvedHash );
+    }
+
+    #[test]
+    fn convert_to_three_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([("ONE".to_string(),"Ünïcödé ~z~".to_string())]),
+            IndexMap::from([("AUX1".to_string(),
+                    IndexMap::from([("ONE".to_string(),"Dup".to_string()),("TWO".to_string(),"Smörgåsbord".to_string())]))]),
+            );
+
+        assert!( x.convert_to(GXTFileFormat::Three, &Default::default()).is_err() );
+
+        let mut options = GXTConversionOptions { merge_aux_tables: true, ..Default::default() };
+        options.write_options.fallback = Some(crate::GXTFallbackPolicy { strip_diacritics: true, ..Default::default() });
+
+        let (three, report) = x.convert_to(GXTFileFormat::Three, &options).unwrap();
+        assert!( three.aux_tables.is_empty() );
+        assert!( three.main_table["ONE"] == "Ünïcödé " );
+        assert!( three.main_table["TWO"] == "Smörgasbord" );
+        assert!( report.merged_tables == vec!("AUX1".to_string()) );
+        assert!( report.dropped == vec!(GXTDroppedString { table: "AUX1".to_string(), key: "ONE".to_string(), reason: GXTDropReason::DuplicateKey }) );
+        assert!( report.unmapped_tags.len() == 1 && report.unmapped_tags[0].tag == "z" );
+        assert!( report.substitutions["AUX1"]["TWO"][0].original == 'å' );
+    }
+}
@@ -0,0 +1,297 @@
+//! This module is used to compare two GXT files by their contents, rather than line by line.
+//!
+//! Decompiled text files of the same GXT file may list their strings in a different order (see
+//! [crate::ImportOrdering]), which makes a regular text diff hard to read. [diff] matches strings
+//! by name instead, and returns a [GXTDiff] listing the strings added, removed and changed in
+//! each table, as well as tables that were added, removed or renamed.
+//!
+//! In GTA SA files, string names are stored as CRC32 hashes, so the same string may be called
+//! `#1A2B3C4D` in one file and by its actual name in another. Such strings are matched by their
+//! hash, and shown under their readable name, taken from either file or from a name list.
+//!
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fmt;
+use crate::{GXTFile, GXTFileFormat};
+use crate::string_to_name_crc32;
+
+/// The old and new value of a string that changed.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GXTChangedString {
+    /// The value in the old file.
+    pub old: String,
+    /// The value in the new file.
+    pub new: String,
+}
+
+/// Describes what happened to a table between the two files.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GXTTableStatus {
+    /// The table only exists in the new file, so all of its strings are added.
+    Added,
+    /// The table only exists in the old file, so all of its strings are removed.
+    Removed,
+    /// The table exists in the old file under another name, with the same string names.
+    Renamed {
+        /// The name in the old file.
+        from: String,
+    },
+    /// The table exists in both files.
+    Changed,
+}
+
+/// The differences in a single table.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GXTTableDiff {
+    /// The name of the table (in the new file, unless it was removed).
+    pub table: String,
+    /// What happened to the table.
+    pub status: GXTTableStatus,
+    /// Strings that only exist in the new file.
+    pub added: IndexMap<String,String>,
+    /// Strings that only exist in the old file.
+    pub removed: IndexMap<String,String>,
+    /// Strings that have different values.
+    pub changed: IndexMap<String,GXTChangedString>,
+}
+
+impl GXTTableDiff {
+    /// Returns true if the table has no differences other than possibly being renamed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The differences between two files, as returned by [diff].
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GXTDiff {
+    /// Every table with differences, in the order of the new file, followed by removed tables.
+    pub tables: Vec<GXTTableDiff>,
+}
+
+impl GXTDiff {
+    /// Returns true if the files have the same contents.
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+impl fmt::Display for GXTDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for t in &self.tables {
+            match &t.status {
+                GXTTableStatus::Added => writeln!(f, "[{}] (added)", t.table)?,
+                GXTTableStatus::Removed => writeln!(f, "[{}] (removed)", t.table)?,
+                GXTTableStatus::Renamed { from } => writeln!(f, "[{}] (renamed from {})", t.table, from)?,
+                GXTTableStatus::Changed => writeln!(f, "[{}]", t.table)?,
+            }
+            for (k,v) in &t.added {
+                writeln!(f, "+ {k} = {v:?}")?;
+            }
+            for (k,v) in &t.removed {
+                writeln!(f, "- {k} = {v:?}")?;
+            }
+            for (k,v) in &t.changed {
+                writeln!(f, "~ {k} = {:?} -> {:?}", v.old, v.new)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// what identifies a string within a table. in GTA SA files, a name and its hash are the same
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum GXTKeyId {
+    Name(String),
+    Hash(u32),
+}
+
+// true if the name is a CRC32 hash written as #XXXXXXXX, rather than an actual name
+fn is_hash(key: &str) -> bool {
+    key.starts_with('#') && !key.starts_with("##") && key.chars().count() == 9
+}
+
+pub(crate) fn key_id(key: &str, hashed: bool) -> GXTKeyId {
+    match hashed {
+        true => match string_to_name_crc32(key) {
+            Ok(h) => GXTKeyId::Hash(h),
+            Err(_) => GXTKeyId::Name(key.to_string()), // an invalid hash can only match itself
+        },
+        false => GXTKeyId::Name(key.to_string()),
+    }
+}
+
+// picks the most readable name for a string found under these names in the two files
+fn display_name(names: &[&str], id: &GXTKeyId, name_list: &Option<HashMap<u32,String>>) -> String {
+    if let Some(n) = names.iter().find(|n| !is_hash(n)) {
+        return n.to_string();
+    }
+    if let (GXTKeyId::Hash(h), Some(l)) = (id, name_list) && let Some(n) = l.get(h) {
+        return n.clone();
+    }
+    names.first().map(|n| n.to_string()).unwrap_or_default()
+}
+
+// compares the strings of two versions of a table
+fn diff_table(table: &str, status: GXTTableStatus, old: &IndexMap<String,String>, new: &IndexMap<String,String>, hashed: bool, name_list: &Option<HashMap<u32,String>>) -> GXTTableDiff {
+
+    let old_keys: HashMap<GXTKeyId, (&String, &String)> = old.iter().map(|(k,v)| (key_id(k, hashed), (k,v))).collect();
+    let new_keys: HashMap<GXTKeyId, (&String, &String)> = new.iter().map(|(k,v)| (key_id(k, hashed), (k,v))).collect();
+
+    let mut res = GXTTableDiff {
+        table: table.to_string(),
+        status,
+        added: IndexMap::new(),
+        removed: IndexMap::new(),
+        changed: IndexMap::new(),
+    };
+
+    for (k,v) in new {
+        let id = key_id(k, hashed);
+        match old_keys.get(&id) {
+            None => { res.added.insert(display_name(&[k], &id, name_list), v.clone()); },
+            Some((old_k, old_v)) if *old_v != v => {
+                res.changed.insert(display_name(&[k, old_k], &id, name_list), GXTChangedString { old: old_v.to_string(), new: v.clone() });
+            },
+            Some(_) => {},
+        }
+    }
+    for (k,v) in old {
+        let id = key_id(k, hashed);
+        if !new_keys.contains_key(&id) {
+            res.removed.insert(display_name(&[k], &id, name_list), v.clone());
+        }
+    }
+
+    res
+}
+
+/// Compares two files, returning the strings and tables that were added, removed or changed in the
+/// new file. The order of strings and tables doesn't matter.
+///
+/// If either file is in the GTA SA format, strings are matched by the CRC32 hash of their name, so
+/// a hash matches the name it was calculated from. The name list is used to show strings that are
+/// only known by their hash under their actual names.
+pub fn diff(old: &GXTFile, new: &GXTFile, name_list: &Option<HashMap<u32,String>>) -> GXTDiff {
+
+    let is_san = |f: &GXTFileFormat| matches!(f, GXTFileFormat::San8 | GXTFileFormat::San16);
+    let hashed = is_san(&old.format) || is_san(&new.format);
+
+    let old_tables: IndexMap<&str, &IndexMap<String,String>> = old.tables().collect();
+    let new_tables: IndexMap<&str, &IndexMap<String,String>> = new.tables().collect();
+
+    let mut removed_tables: Vec<&str> = old_tables.keys().filter(|t| !new_tables.contains_key(*t)).copied().collect();
+    let mut res = GXTDiff::default();
+
+    // a table that isn't in the old file, but has the same strings as a table that isn't in the
+    // new file, was most likely renamed
+    let key_set = |t: &IndexMap<String,String>| -> std::collections::BTreeSet<GXTKeyId> {
+        t.keys().map(|k| key_id(k, hashed)).collect()
+    };
+
+    for (table, strings) in &new_tables {
+        let table_diff = match old_tables.get(table) {
+            Some(old_strings) => diff_table(table, GXTTableStatus::Changed, old_strings, strings, hashed, name_list),
+            None => {
+                let renamed_from = removed_tables.iter().position(|t| !strings.is_empty() && key_set(old_tables[t]) == key_set(strings));
+                match renamed_from {
+                    Some(i) => {
+                        let from = removed_tables.remove(i);
+                        let table_diff = diff_table(table, GXTTableStatus::Renamed { from: from.to_string() }, old_tables[from], strings, hashed, name_list);
+                        res.tables.push(table_diff);
+                        continue;
+                    },
+                    None => diff_table(table, GXTTableStatus::Added, &IndexMap::new(), strings, hashed, name_list),
+                }
+            },
+        };
+        if !table_diff.is_empty() {
+            res.tables.push(table_diff);
+        }
+    }
+
+    for table in removed_tables {
+        res.tables.push(diff_table(table, GXTTableStatus::Removed, old_tables[table], &IndexMap::new(), hashed, name_list));
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_test() {
+
+        let old = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hello".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+                ("OLD".to_string(),"Old".to_string()),
+            ]),
+            IndexMap::from([
+                ("AUX1".to_string(), IndexMap::from([("A".to_string(),"A".to_string())])),
+                ("GONE".to_string(), IndexMap::from([("G".to_string(),"G".to_string())])),
+            ]),
+            );
+
+        // the same strings in a different order make no difference
+        let mut reordered = old.clone();
+        reordered.main_table.reverse();
+        assert!( diff(&old, &reordered, &None).is_empty() );
+
+        let new = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([
+                ("NEW".to_string(),"New".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+                ("HELLO".to_string(),"Hi".to_string()),
+            ]),
+            IndexMap::from([
+                ("AUX2".to_string(), IndexMap::from([("A".to_string(),"A!".to_string())])),
+                ("ADDED".to_string(), IndexMap::from([("D".to_string(),"D".to_string())])),
+            ]),
+            );
+
+        let d = diff(&old, &new, &None);
+        assert!( d.to_string() == "[MAIN]\n+ NEW = \"New\"\n- OLD = \"Old\"\n~ HELLO = \"Hello\" -> \"Hi\"\n\
+            [AUX2] (renamed from AUX1)\n~ A = \"A\" -> \"A!\"\n\
+            [ADDED] (added)\n+ D = \"D\"\n\
+            [GONE] (removed)\n- G = \"G\"\n" );
+    }
+
+    #[test]
+    fn diff_hash_test() {
+
+        // a decompiled file without a name list only knows the hashes
+        let old = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                (format!("#{:08X}", string_to_name_crc32("HELLO").unwrap()),"Hello".to_string()),
+                (format!("#{:08X}", string_to_name_crc32("BYE").unwrap()),"Bye".to_string()),
+            ]),
+            IndexMap::new(),
+            );
+        let new = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hi".to_string()),
+                ("NEW".to_string(),"New".to_string()),
+            ]),
+            IndexMap::new(),
+            );
+
+        let d = diff(&old, &new, &None);
+        assert!( d.tables.len() == 1 );
+        assert!( d.tables[0].changed == IndexMap::from([("HELLO".to_string(), GXTChangedString { old: "Hello".to_string(), new: "Hi".to_string() })]) );
+        assert!( d.tables[0].added.keys().eq(["NEW"]) );
+        assert!( d.tables[0].removed.keys().eq([&format!("#{:08X}", string_to_name_crc32("BYE").unwrap())]) );
+
+        // with a name list, the removed string gets its name back
+        let name_list = Some(HashMap::from([(string_to_name_crc32("BYE").unwrap(), "BYE".to_string())]));
+        assert!( diff(&old, &new, &name_list).tables[0].removed.keys().eq(["BYE"]) );
+    }
+}
@@ -0,0 +1,297 @@
+//! This module is used to infer a custom character table from a GXT file, using a known-correct
+//! transcription of some of its strings.
+//!
+//! Bootleg translations of the games often reuse character codes for letters of other alphabets,
+//! and the only way to figure out the mapping is to compare the encoded strings against what the
+//! game actually shows on screen. Given a GXT file and a [GXTReferenceText] containing the correct
+//! Unicode text for a subset of its keys, [infer_character_table] aligns each pair of strings
+//! character by character and builds a [GXTCharacterTable] from the results.
+//!
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::prelude::*;
+use indexmap::IndexMap;
+use crate::{GXTCharacterTable, GXTError, GXTFile, GXTFileFormat, ImportOrdering, MAIN_TABLE_NAME, decode_character, encode_character, parse_toml};
+
+/// Contains the reference text for a subset of a GXT file's strings. It uses the same layout as the
+/// main_table and aux_tables sections of a TOML-based text file, so a decompiled file with its
+/// strings corrected by hand can be used directly. Any "format" field is ignored.
+#[derive(serde::Serialize,serde::Deserialize,Default)]
+pub struct GXTReferenceText {
+
+    /// Reference strings for the main table, keyed by their names (or hashes).
+    #[serde(default)]
+    pub main_table: IndexMap<String,String>,
+
+    /// Reference strings for the auxiliary tables, keyed by table name, then by string name.
+    #[serde(default)]
+    pub aux_tables: IndexMap<String,IndexMap<String,String>>,
+}
+
+/// Describes a character that one of the reference strings suggested for a specific code.
+pub struct GXTInferenceCandidate {
+
+    /// The suggested Unicode character.
+    pub character: char,
+
+    /// The locations (table name and string name) where this character was seen.
+    pub locations: Vec<(String,String)>,
+}
+
+/// Describes a character code that the reference text maps to more than one character.
+pub struct GXTInferenceConflict {
+
+    /// The character code, as used in the GXT file.
+    pub code: u16,
+
+    /// The character that was written into the inferred table (the most frequently seen one).
+    pub chosen: char,
+
+    /// All the characters that were suggested for this code.
+    pub candidates: Vec<GXTInferenceCandidate>,
+}
+
+/// Describes why a reference string could not be used for inference.
+pub enum GXTInferenceSkipReason {
+    /// The GXT file does not have a table with this name.
+    MissingTable,
+    /// The GXT file does not have a string with this name in the table.
+    MissingKey,
+    /// The GXT string and the reference string have a different number of characters, so they
+    /// can't be aligned.
+    LengthMismatch {
+        /// Number of characters in the GXT file's string.
+        gxt_length: usize,
+        /// Number of characters in the reference string.
+        reference_length: usize,
+    },
+}
+
+/// Describes a reference string that was not used for inference.
+pub struct GXTInferenceSkip {
+    /// The name of the table the string was listed in.
+    pub table: String,
+    /// The name of the string.
+    pub key: String,
+    /// The reason why the string was skipped.
+    pub reason: GXTInferenceSkipReason,
+}
+
+/// The result of a character table inference.
+pub struct GXTTableInference {
+
+    /// The inferred character table. Only codes that decode into something other than the
+    /// format's default character are listed, so unchanged characters fall back to the default
+    /// table, as usual.
+    pub table: GXTCharacterTable,
+
+    /// The format of the GXT file the table was inferred from.
+    pub format: GXTFileFormat,
+
+    /// Codes that were aligned with more than one different character.
+    pub conflicts: Vec<GXTInferenceConflict>,
+
+    /// Reference strings that could not be aligned with the GXT file.
+    pub skipped: Vec<GXTInferenceSkip>,
+}
+
+impl fmt::Display for GXTInferenceSkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GXTInferenceSkipReason::MissingTable => write!(f, "table does not exist in the GXT file"),
+            GXTInferenceSkipReason::MissingKey => write!(f, "string does not exist in the GXT file"),
+            GXTInferenceSkipReason::LengthMismatch { gxt_length, reference_length } => write!(f,
+                "GXT string has {} characters, reference string has {}", gxt_length, reference_length),
+        }
+    }
+}
+
+/// Read reference text from a TOML file. See [GXTReferenceText] for details on the format.
+pub fn read_reference_text(file: &mut (impl Read + std::io::Seek)) -> Result<GXTReferenceText,GXTError> {
+
+    let mut raw_data: String = Default::default();
+    file.read_to_string(&mut raw_data)?;
+
+    let reference: GXTReferenceText = parse_toml(&raw_data)?;
+    Ok(reference)
+}
+
+/// Infer a custom character table by aligning the strings of a GXT file with reference text.
+///
+/// The GXT file is read using the default character table for its format. Each reference string
+/// is then compared to the GXT string with the same table and name, character by character, and
+/// every code that is aligned with a character other than its default decoding is added to the
+/// resulting table's \[decode_table\]. Strings that can't be aligned (because they don't exist or
+/// have a different length) are listed in the result, as are codes that were aligned with
+/// different characters in different places -- in that case, the most common character wins.
+///
+/// The resulting table can be written using [crate::write_custom_table], and read back using
+/// [crate::read_custom_table].
+pub fn infer_character_table(file: &mut (impl Read + std::io::Seek), reference: &GXTReferenceText) -> Result<GXTTableInference,GXTError> {
+
+    let gxt = GXTFile::read_from_gxt(file, &Some(ImportOrdering::Native), &None, &None)?;
+
+    // for every code, count which characters it was aligned with, and where
+    let mut observations: BTreeMap<u16, BTreeMap<char, Vec<(String,String)>>> = BTreeMap::new();
+    let mut skipped: Vec<GXTInferenceSkip> = vec!();
+
+    let reference_tables = std::iter::once((MAIN_TABLE_NAME, &reference.main_table))
+        .chain(reference.aux_tables.iter().map(|(k,v)| (k.as_str(), v)));
+
+    for (table_name, reference_table) in reference_tables {
+
+        let gxt_table = if table_name == MAIN_TABLE_NAME {
+            Some(&gxt.main_table)
+        } else {
+            gxt.aux_tables.get(table_name)
+        };
+
+        for (key, reference_string) in reference_table {
+
+            let skip = |reason| GXTInferenceSkip { table: table_name.to_string(), key: key.clone(), reason };
+
+            let Some(gxt_table) = gxt_table else {
+                skipped.push(skip(GXTInferenceSkipReason::MissingTable));
+                continue;
+            };
+
+            let Some(gxt_string) = gxt_table.get(key) else {
+                skipped.push(skip(GXTInferenceSkipReason::MissingKey));
+                continue;
+            };
+
+            let gxt_length = gxt_string.chars().count();
+            let reference_length = reference_string.chars().count();
+            if gxt_length != reference_length {
+                skipped.push(skip(GXTInferenceSkipReason::LengthMismatch { gxt_length, reference_length }));
+                continue;
+            }
+
+            for (g, r) in gxt_string.chars().zip(reference_string.chars()) {
+                // the default tables are one-to-one, so re-encoding the decoded character always
+                // recovers the original code
+                let code = encode_character(g, &gxt.format, &None)?;
+                observations.entry(code).or_default().entry(r).or_default()
+                    .push((table_name.to_string(), key.clone()));
+            }
+        }
+    }
+
+    let mut table = GXTCharacterTable {
+        decode_table: HashMap::new(),
+        encode_table: HashMap::new(),
+    };
+    let mut conflicts: Vec<GXTInferenceConflict> = vec!();
+
+    for (code, characters) in observations {
+
+        // most common character first, ties are resolved by codepoint to keep the output stable
+        let mut candidates: Vec<GXTInferenceCandidate> = characters.into_iter()
+            .map(|(character, locations)| GXTInferenceCandidate { character, locations })
+            .collect();
+        candidates.sort_by(|a,b| b.locations.len().cmp(&a.locations.len()).then(a.character.cmp(&b.character)));
+
+        let chosen = candidates[0].character;
+
+        if chosen != decode_character(code, &gxt.format, &None) {
+            table.decode_table.insert(code, chosen);
+        }
+
+        if candidates.len() > 1 {
+            conflicts.push(GXTInferenceConflict { code, chosen, candidates });
+        }
+    }
+
+    // same as read_custom_table, but with a predictable result when several codes share a
+    // character: the lowest code is used for encoding
+    let mut decode_entries: Vec<(&u16,&char)> = table.decode_table.iter().collect();
+    decode_entries.sort();
+    for (k,v) in decode_entries {
+        table.encode_table.entry(*v).or_insert(*k);
+    }
+
+    Ok(GXTTableInference {
+        table,
+        format: gxt.format,
+        conflicts,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{BufReader, Cursor};
+    use super::*;
+
+    #[test]
+    fn infer_vc_russian_table_test() {
+
+        let _f = File::open("character_tables/vc_ru_tycoon.toml").expect("Unable to open character table");
+        let mut file = BufReader::new(_f);
+        let custom_table = Some(crate::read_custom_table(&mut file).expect("Unable to read character table"));
+
+        let _f = File::open("test_files/gtavc_ru.txt").expect("Unable to open text file");
+        let mut file = BufReader::new(_f);
+        let gxt = GXTFile::read_from_text(&mut file).expect("Unable to load GXT data from text file");
+
+        let mut compiled_data: Vec<u8> = vec!();
+        gxt.write_to_gxt(&mut compiled_data,&custom_table).expect("Unable to compile GXT file");
+
+        let reference = GXTReferenceText {
+            main_table: gxt.main_table.clone(),
+            aux_tables: gxt.aux_tables.clone(),
+        };
+        let inference = infer_character_table(&mut Cursor::new(&compiled_data), &reference).expect("Unable to infer character table");
+
+        assert!( inference.skipped.is_empty() );
+
+        // the latin "e" in "DEF" is uppercase, so it doesn't clash with the cyrillic "е"
+        assert!( inference.conflicts.is_empty() );
+        assert!( inference.table.decode_table.get(&152) == Some(&'в') );
+        assert!( inference.table.decode_table.get(&101) == Some(&'е') );
+        assert!( !inference.table.decode_table.contains_key(&(b'D' as u16)) );
+
+        // decompiling with the inferred table must give back the reference text
+        let inferred_table = Some(inference.table);
+        let x = GXTFile::read_from_gxt(&mut Cursor::new(&compiled_data),&None,&inferred_table,&None).expect("Unable to decompile GXT file");
+        assert!( x.main_table == reference.main_table );
+    }
+
+    #[test]
+    fn infer_conflicts_and_skips_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("A".to_string(),"ab".to_string()),("B".to_string(),"ab".to_string()),("C".to_string(),"ab".to_string())]),
+            IndexMap::new(),
+            );
+
+        let mut compiled_data: Vec<u8> = vec!();
+        x.write_to_gxt(&mut compiled_data,&None).expect("Unable to compile GXT file");
+
+        let reference = GXTReferenceText {
+            main_table: IndexMap::from([
+                ("A".to_string(),"аb".to_string()),
+                ("B".to_string(),"аb".to_string()),
+                ("C".to_string(),"xb".to_string()),
+                ("D".to_string(),"ab".to_string()),
+            ]),
+            aux_tables: IndexMap::from([("NOPE".to_string(), IndexMap::from([("A".to_string(),"ab".to_string())]))]),
+        };
+
+        let inference = infer_character_table(&mut Cursor::new(&compiled_data), &reference).expect("Unable to infer character table");
+
+        assert!( inference.skipped.len() == 2 );
+        assert!( inference.conflicts.len() == 1 );
+        assert!( inference.conflicts[0].code == b'a' as u16 );
+        assert!( inference.conflicts[0].chosen == 'а' );
+        assert!( inference.table.decode_table.len() == 1 );
+
+        let mut written: Vec<u8> = vec!();
+        crate::write_custom_table(&inference.table, &mut written).expect("Unable to write character table");
+        let read_back = crate::read_custom_table(&mut Cursor::new(&written)).expect("Unable to read character table");
+        assert!( read_back.decode_table == inference.table.decode_table );
+        assert!( read_back.encode_table == inference.table.encode_table );
+    }
+}
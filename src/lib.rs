@@ -22,8 +22,26 @@ use std::io::prelude::*;
 use thiserror::Error;
 use indexmap::IndexMap;
 use std::collections::HashMap;
-
-#[derive(serde::Serialize,serde::Deserialize,Clone)]
+use unicode_normalization::UnicodeNormalization;
+
+pub mod consistency;
+pub mod conversion;
+pub mod diff;
+pub mod inference;
+pub mod lint;
+pub mod merge;
+pub mod patch;
+pub mod project;
+pub mod search;
+pub mod stats;
+pub mod tags;
+pub mod validation;
+
+/// The name of the main table, as written in the TABL section of VC and SA format files. It is also
+/// used to refer to the main table in reports produced by this crate.
+pub const MAIN_TABLE_NAME: &str = "MAIN";
+
+#[derive(serde::Serialize,serde::Deserialize,Clone,Copy,Debug,PartialEq,Eq)]
 /// Specifies one of the possible formats to be used when creating or loading a GXT file
 pub enum GXTFileFormat {
     /// GTA III, GTA: Vice City (Xbox)
@@ -51,30 +69,303 @@ pub enum ImportOrdering {
     Offset, 
 }
 
-/// Describes the possible errors that can be returned by the program
+/// Describes the possible errors that can be returned by the program.
+///
+/// Errors found while parsing a GXT file carry the offset in the file where the problem was found,
+/// errors found while compiling one carry the table and string they concern (when known), and
+/// errors in TOML files carry the location in the text.
 #[derive(Error, Debug)]
 pub enum GXTError {
-    /// Error during parsing of a GXT file
-    #[error("GXT file parsing error: {0}")]
-    ParsingError(String),
-    /// Error during compilation of a GXT file
-    #[error("GXT file compilation error: {0}")]
-    CompilationError(String),
+    /// The file doesn't start like any of the known GXT formats.
+    #[error("GXT file parsing error: the file does not match any known GTA 3 / VC / SA format")]
+    UnknownFormat,
+    /// A San Andreas format file has a version other than 4.
+    #[error("GXT file parsing error: the file has version {0}, must have version 4")]
+    UnsupportedVersion(u16),
+    /// A San Andreas format file has a character size other than 8 or 16 bits.
+    #[error("GXT file parsing error: the file has character size {0}, must have 8 or 16")]
+    UnsupportedCharacterSize(u16),
+    /// A section of the file doesn't start with the expected header.
+    #[error("GXT file parsing error{}: invalid {section} header at offset {offset:#X}", fmt_location(table, &None))]
+    InvalidHeader {
+        /// The section's name (TABL, TKEY or TDAT).
+        section: &'static str,
+        /// The offset in the file where the header was expected.
+        offset: u64,
+        /// The table the section belongs to, if any.
+        table: Option<String>,
+    },
+    /// The file ends in the middle of a section.
+    #[error("GXT file parsing error{}: unexpected end of file while reading {section} at offset {offset:#X}", fmt_location(table, key))]
+    UnexpectedEnd {
+        /// The section's name (TABL, TKEY or TDAT).
+        section: &'static str,
+        /// The offset of the section (or, in TDAT, of the string) that couldn't be read whole.
+        offset: u64,
+        /// The table the section belongs to, if any.
+        table: Option<String>,
+        /// The string being read, if any.
+        key: Option<String>,
+    },
+    /// The entries of a TABL or TKEY section aren't sorted the way the games expect (only checked
+    /// by [GXTFile::read_from_gxt_strict]).
+    #[error("GXT file parsing error{}: {section} entries at offset {offset:#X} are not sorted", fmt_location(table, &None))]
+    UnsortedEntries {
+        /// The section's name (TABL or TKEY).
+        section: &'static str,
+        /// The offset of the section.
+        offset: u64,
+        /// The table the section belongs to, if any.
+        table: Option<String>,
+    },
+    /// An offset in the file points outside of the file, or outside of its section.
+    #[error("GXT file parsing error{}: {section} entry at offset {offset:#X} points outside of the {}", fmt_location(table, key), if *section == "TDAT" { "TDAT section" } else { "file" })]
+    OutOfBounds {
+        /// The section the offset points into.
+        section: &'static str,
+        /// The offset of the section containing the entry (or, in TDAT, of the string).
+        offset: u64,
+        /// The table the entry belongs to, if any.
+        table: Option<String>,
+        /// The string the entry belongs to, if any.
+        key: Option<String>,
+    },
+    /// A string in the file is longer than [MAX_STRING_LENGTH] characters.
+    #[error("GXT file parsing error{}: string at offset {offset:#X} is longer than {MAX_STRING_LENGTH} characters", fmt_location(table, key))]
+    StringTooLong {
+        /// The offset of the string.
+        offset: u64,
+        /// The table the string belongs to.
+        table: Option<String>,
+        /// The string's name.
+        key: Option<String>,
+    },
+    /// The file has more tables than [MAX_TABLES], more strings than [MAX_ENTRIES], or more text
+    /// than [MAX_TOTAL_LENGTH]. Such a file can only be corrupt, or made to use up memory.
+    #[error("GXT file parsing error{}: the file has more than {limit} {what}", fmt_location(table, &None))]
+    TooLarge {
+        /// What there is too much of.
+        what: &'static str,
+        /// The limit that was reached.
+        limit: usize,
+        /// The table being read when the limit was reached, if any.
+        table: Option<String>,
+    },
+    /// The first table listed in the TABL section isn't the main table.
+    #[error("GXT file parsing error: the first table must be {MAIN_TABLE_NAME}")]
+    MissingMainTable,
+    /// A string or table name is too long to be stored in the file.
+    #[error("GXT file compilation error{}: name {name} can't be longer than 8 bytes", fmt_location(table, &None))]
+    NameTooLong {
+        /// The name itself.
+        name: String,
+        /// The table the string belongs to, or None if the name is a table's.
+        table: Option<String>,
+    },
+    /// A hash-based string name (#XXXXXXXX) is not valid.
+    #[error("GXT file compilation error{}: hash-based string name {name} does not contain a valid hex value", fmt_location(table, &None))]
+    InvalidHash {
+        /// The name itself.
+        name: String,
+        /// The table the string belongs to.
+        table: Option<String>,
+    },
+    /// Two strings in the same table have the same name, or names with the same CRC32 hash (which
+    /// includes a name and its own #XXXXXXXX hash).
+    #[error("GXT file compilation error{}: string name {key} is the same as or has the same hash as {other}", fmt_location(table, &None))]
+    DuplicateName {
+        /// The name of the second string.
+        key: String,
+        /// The name of the string that came first.
+        other: String,
+        /// The table both strings belong to.
+        table: Option<String>,
+    },
+    /// A character can't be encoded using the format's character table.
+    #[error("GXT file compilation error{}: character {character:?} (U+{:04X}) cannot be encoded", fmt_location(table, key), u32::from(*character))]
+    UnencodableCharacter {
+        /// The character itself.
+        character: char,
+        /// The table the string belongs to.
+        table: Option<String>,
+        /// The string the character is in.
+        key: Option<String>,
+    },
+    /// A character is encoded as a code that doesn't fit into an 8-bit format file.
+    #[error("GXT file compilation error{}: character {character:?} (U+{:04X}) is to be encoded as {code:04X}, but the 8-bit format GXT file can only encode characters below 255", fmt_location(table, key), u32::from(*character))]
+    CharacterOutOfRange {
+        /// The character itself.
+        character: char,
+        /// The code it would be encoded as.
+        code: u16,
+        /// The table the string belongs to.
+        table: Option<String>,
+        /// The string the character is in.
+        key: Option<String>,
+    },
+    /// A GTA III format file has auxiliary tables.
+    #[error("GXT file compilation error: a GTA III format file cannot have auxiliary tables")]
+    AuxTablesNotAllowed,
+    /// The compiled file would go over one of the limits the reader enforces ([MAX_STRING_LENGTH],
+    /// [MAX_TABLES], [MAX_ENTRIES] or [MAX_TOTAL_LENGTH]), so it couldn't be read back.
+    #[error("GXT file compilation error{}: more than {limit} {what}, which can't be read back", fmt_location(table, key))]
+    TooLargeToRead {
+        /// What there is too much of.
+        what: &'static str,
+        /// The limit that was exceeded.
+        limit: usize,
+        /// The table where the limit was exceeded.
+        table: Option<String>,
+        /// The string that is too long, if that's the limit exceeded.
+        key: Option<String>,
+    },
+    /// A section of the compiled file would be larger than its 32-bit size field can hold.
+    #[error("GXT file compilation error{}: {section} section would be {size} bytes long, more than a GXT file can hold", fmt_location(table, &None))]
+    SectionTooLarge {
+        /// The section that is too large.
+        section: &'static str,
+        /// Its size in bytes.
+        size: u64,
+        /// The table it belongs to.
+        table: Option<String>,
+    },
+    /// A table would start at an offset that a 32-bit `TABL` entry cannot hold.
+    #[error("GXT file compilation error{}: table would start at offset {offset:#X}, past what a GXT file can address", fmt_location(table, &None))]
+    OffsetTooLarge {
+        /// The offset the table would start at.
+        offset: u64,
+        /// The table's name.
+        table: Option<String>,
+    },
+    /// A string or table differs between two files being merged, and the [merge::MergePolicy]
+    /// decided to fail.
+    #[error("GXT file merge error{}: the files have different versions of this {}", fmt_location(&Some(table.clone()), key), if key.is_some() { "string" } else { "table" })]
+    MergeConflict {
+        /// The table in conflict (or containing the string in conflict).
+        table: String,
+        /// The string in conflict, or None if the whole table is.
+        key: Option<String>,
+    },
+    /// The files being merged are of different formats.
+    #[error("GXT file merge error: the files have different formats ({ours:?} and {theirs:?})")]
+    MergeFormatMismatch {
+        /// The format of the file being merged into.
+        ours: GXTFileFormat,
+        /// The format of the other file.
+        theirs: GXTFileFormat,
+    },
+    /// A string doesn't have the value a [patch::GXTPatch] expects it to have.
+    #[error("GXT patch error{}: expected {expected:?}, found {}", fmt_location(&Some(table.clone()), &Some(key.clone())), match found { Some(f) => format!("{f:?}"), None => "no string".to_string() })]
+    PatchMismatch {
+        /// The table containing the string.
+        table: String,
+        /// The string's name.
+        key: String,
+        /// The value the patch expects.
+        expected: String,
+        /// The actual value, or None if there is no such string.
+        found: Option<String>,
+    },
+    /// A string or table that a [patch::GXTPatch] changes doesn't exist.
+    #[error("GXT patch error{}: the {} does not exist", fmt_location(&Some(table.clone()), key), if key.is_some() { "string" } else { "table" })]
+    PatchNotFound {
+        /// The table (or the table containing the string).
+        table: String,
+        /// The string's name, or None if the table itself doesn't exist.
+        key: Option<String>,
+    },
+    /// A table that a [patch::GXTPatch] adds already exists.
+    #[error("GXT patch error in [{table}]: the table already exists")]
+    PatchTableExists {
+        /// The table's name.
+        table: String,
+    },
+    /// An internal inconsistency in the library. This should never happen.
+    #[error("internal error: {0}")]
+    InternalError(String),
     /// Error from the I/O functions
-    #[error("I/O error")]
+    #[error("I/O error: {0}")]
     IOError(#[from] std::io::Error),
     /// Error from the TOML serializer
-    #[error("TOML serialization error")]
+    #[error("TOML serialization error: {0}")]
     TOMLSerError(#[from] toml::ser::Error),
-    /// Error from the TOML deserializer
-    #[error("TOML deserialization error")]
-    TOMLDeError(#[from] toml::de::Error),
+    /// Error from the TOML deserializer, along with its location in the text
+    #[error("TOML deserialization error{}: {message}", match (line, column) { (Some(l), Some(c)) => format!(" at line {l}, column {c}"), _ => String::new() })]
+    TOMLDeError {
+        /// The error message, without the location.
+        message: String,
+        /// The byte range in the text where the error was found.
+        span: Option<std::ops::Range<usize>>,
+        /// The line where the error was found, starting from 1.
+        line: Option<usize>,
+        /// The column where the error was found (in characters), starting from 1.
+        column: Option<usize>,
+    },
+}
+
+// describes where an error happened, for use in error messages
+fn fmt_location(table: &Option<String>, key: &Option<String>) -> String {
+    match (table, key) {
+        (Some(t), Some(k)) => format!(" in [{t}] {k}"),
+        (Some(t), None) => format!(" in [{t}]"),
+        (None, Some(k)) => format!(" in {k}"),
+        (None, None) => String::new(),
+    }
+}
+
+impl GXTError {
+    // fills in the table and string an error concerns, unless it already has them
+    fn with_location(mut self, table_name: &str, key_name: Option<&str>) -> GXTError {
+        match &mut self {
+            GXTError::NameTooLong { table, .. } | GXTError::InvalidHash { table, .. } => {
+                table.get_or_insert_with(|| table_name.to_string());
+            },
+            GXTError::UnencodableCharacter { table, key, .. }
+            | GXTError::CharacterOutOfRange { table, key, .. }
+            | GXTError::TooLargeToRead { table, key, .. } => {
+                table.get_or_insert_with(|| table_name.to_string());
+                if let Some(k) = key_name { key.get_or_insert_with(|| k.to_string()); }
+            },
+            _ => {},
+        }
+        self
+    }
+}
+
+// parses a TOML text, keeping the location of any error
+fn parse_toml<T: serde::de::DeserializeOwned>(raw_data: &str) -> Result<T,GXTError> {
+
+    toml::from_str(raw_data).map_err(|e: toml::de::Error| {
+        let span = e.span();
+        let (line, column) = match &span {
+            Some(r) => {
+                let before = &raw_data[..r.start.min(raw_data.len())];
+                let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+                (Some(before.matches('\n').count() + 1), Some(before[line_start..].chars().count() + 1))
+            },
+            None => (None, None),
+        };
+        GXTError::TOMLDeError { message: e.message().to_string(), span, line, column }
+    })
+}
+
+// turns an unexpected end of file into an error describing what was being read
+fn truncated(error: std::io::Error, section: &'static str, offset: u64, table: &Option<String>, key: Option<&str>) -> GXTError {
+    match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => GXTError::UnexpectedEnd {
+            section,
+            offset,
+            table: table.clone(),
+            key: key.map(|k| k.to_string()),
+        },
+        _ => error.into(),
+    }
 }
 
 /// This structure contains all the data that a GXT file can store, in an easy developer-readable
 /// form. Functions that read the data from a GXT or TOML file return this structure, while
 /// functions that export a GXT or TOML file require it as a parameter.
-#[derive(serde::Serialize,serde::Deserialize)]
+#[derive(serde::Serialize,serde::Deserialize,Clone,Debug,PartialEq)]
 pub struct GXTFile {
 
     /// Specifies the format used when decompiling or compiling the GXT file.
@@ -113,6 +404,124 @@ pub struct GXTCharacterTable {
     pub encode_table: HashMap<char, u16>,
 }
 
+/// This structure describes what to do with characters that can't be encoded in the GXT file's
+/// format, either by the default character table or the custom one. Without a fallback policy,
+/// compiling a string with such a character fails.
+///
+/// When a character can't be encoded, it is first looked up in the transliteration map, and if it
+/// isn't there, its diacritics are stripped (if enabled). Every substitution made this way is
+/// listed in the [GXTWriteReport] returned by [GXTFile::write_to_gxt_with_options].
+#[derive(serde::Serialize,serde::Deserialize,Default,Clone)]
+pub struct GXTFallbackPolicy {
+
+    /// Maps characters to strings that should be used instead (like "“" to "\"" or "Ł" to "L").
+    /// The replacement may be longer than one character, or even empty, in which case the
+    /// character is removed. Replacements are not transliterated any further, so every character
+    /// in them must be encodable.
+    #[serde(default)]
+    pub transliteration: HashMap<char, String>,
+
+    /// If set, characters that aren't in the transliteration map are decomposed into a base
+    /// character and combining diacritical marks (Unicode canonical decomposition), and the marks
+    /// are removed, so "ą" becomes "a". Combining marks on their own are removed altogether.
+    #[serde(default)]
+    pub strip_diacritics: bool,
+}
+
+impl GXTFallbackPolicy {
+    /// Returns the string that should replace an unsupported character, or None if the policy
+    /// doesn't offer one.
+    pub fn substitute(&self, character: char) -> Option<String> {
+
+        if let Some(s) = self.transliteration.get(&character) {
+            return Some(s.clone());
+        }
+
+        if self.strip_diacritics {
+            let stripped: String = std::iter::once(character).nfd()
+                .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+                .collect();
+            if stripped != character.to_string() {
+                return Some(stripped);
+            }
+        }
+
+        None
+    }
+}
+
+/// Specifies a Unicode normalization form that strings are converted to before being encoded.
+///
+/// Text editors don't agree on whether an accented letter should be stored as one precomposed
+/// character or as a base letter followed by a combining mark, while the games' character tables
+/// only contain precomposed characters. NFC is therefore the most useful form.
+#[derive(serde::Serialize,serde::Deserialize,Clone,Copy,Debug,PartialEq,Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition ("e" followed by a combining acute accent becomes "é").
+    NFC,
+    /// Canonical decomposition ("é" becomes "e" followed by a combining acute accent).
+    NFD,
+    /// Compatibility composition, which also replaces characters like ligatures or superscripts
+    /// with their plain equivalents.
+    NFKC,
+    /// Compatibility decomposition.
+    NFKD,
+    /// Try both canonical forms: strings are converted to NFC, but characters that can't be
+    /// encoded are encoded in their NFD form instead, if possible.
+    Auto,
+}
+
+/// Describes a single character that was replaced by a [GXTFallbackPolicy] during compilation.
+#[derive(Clone, Debug)]
+pub struct GXTSubstitution {
+    /// The character's position in the original string, counted in characters, not bytes. If a
+    /// normalization form was used, the position refers to the normalized string.
+    pub position: usize,
+    /// The character that couldn't be encoded.
+    pub original: char,
+    /// The string that was encoded instead.
+    pub replacement: String,
+}
+
+/// Options that change how a GXT file is compiled, used by [GXTFile::write_to_gxt_with_options].
+#[derive(Clone)]
+pub struct WriteOptions<'a> {
+
+    /// An optional custom character table, same as the one used by [GXTFile::write_to_gxt].
+    pub custom_table: &'a Option<GXTCharacterTable>,
+
+    /// What to do with characters that can't be encoded. If None, compilation fails on the first
+    /// such character.
+    pub fallback: Option<GXTFallbackPolicy>,
+
+    /// The Unicode normalization form every string is converted to before encoding. If None,
+    /// strings are encoded exactly as they are.
+    pub normalization: Option<NormalizationForm>,
+
+    /// The game engine's limits, checked by [GXTFile::validate]. Compilation itself ignores them,
+    /// as a file that goes over them is still a valid GXT file. If None, no limits are checked.
+    pub limits: Option<validation::GXTLimits>,
+}
+
+impl Default for WriteOptions<'_> {
+    fn default() -> Self {
+        WriteOptions {
+            custom_table: &None,
+            fallback: None,
+            normalization: None,
+            limits: None,
+        }
+    }
+}
+
+/// Contains information about a successful compilation that the caller may want to know about.
+#[derive(Default, Debug)]
+pub struct GXTWriteReport {
+    /// Every substitution made by the fallback policy, grouped by table name (the main table is
+    /// called [MAIN_TABLE_NAME]), then by string name.
+    pub substitutions: IndexMap<String, IndexMap<String, Vec<GXTSubstitution>>>,
+}
+
 /// helper function used to avoid serializing aux_tables if there are none
 fn aux_tables_are_empty(table: &IndexMap<String,IndexMap<String,String>>) -> bool {
     return table.len() == 0;
@@ -121,7 +530,7 @@ fn aux_tables_are_empty(table: &IndexMap<String,IndexMap<String,String>>) -> boo
 // -- internal structures, not recommended for use
 
 /// Describes how a string's name may be encoded in the GXT file
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 enum GXTStringName {
     /// Text format (III / VC)
     Text([u8;8]),
@@ -245,12 +654,13 @@ fn encode_character(character: char, format: &GXTFileFormat, custom_table: &Opti
     };
 
     let char_code = character as u32;
+    // the ranges below guarantee that the codes fit into 16 bits
     if char_code < 32 { //characters between 0 and 31
-        Ok(char_code.try_into().unwrap())
+        Ok(char_code as u16)
     } else if (char_code >= 0xE020) && (char_code <= 0xE0FF) { //PUA-based code for 32~255
-        Ok((char_code - 0xE000).try_into().unwrap()) 
+        Ok((char_code - 0xE000) as u16)
     } else if (char_code >= 0xF0000) && (char_code <= 0xFFEFF) { //PUA-based code for 16-bit chars
-        Ok((char_code - 0xFEF00).try_into().unwrap())
+        Ok((char_code - 0xFEF00) as u16)
     } else {
         
         if let Some(v) = custom_table {
@@ -264,29 +674,104 @@ fn encode_character(character: char, format: &GXTFileFormat, custom_table: &Opti
             let (i, c) : (usize, char) = item;
             if (c as u32) == char_code { return Ok(32 + (i as u16)); }
         }
-        return Err(GXTError::CompilationError(format!("Codepoint with incompatible value U+{:04X} found",u32::from(character))));
+        Err(GXTError::UnencodableCharacter { character, table: None, key: None })
     }
 }
 
-fn encode_string(string: &str, format: &GXTFileFormat, custom_table: &Option<GXTCharacterTable>) -> Result<Vec<u8>,GXTError> {
+// same as encode_character, but also makes sure the result fits into the format's character size
+fn encode_character_for_format(character: char, format: &GXTFileFormat, custom_table: &Option<GXTCharacterTable>) -> Result<u16,GXTError> {
+
+    let widechar = encode_character(character, format, custom_table)?;
+    match format {
+        GXTFileFormat::San8 if widechar >= 256 => {
+            Err(GXTError::CharacterOutOfRange { character, code: widechar, table: None, key: None })
+        },
+        _ => Ok(widechar),
+    }
+}
+
+// encodes a string into character codes, without stopping at characters that can't be encoded.
+// those are skipped and returned separately, along with their positions and the reason.
+fn encode_codes(string: &str, format: &GXTFileFormat, options: &WriteOptions, substitutions: &mut Vec<GXTSubstitution>) -> (Vec<u16>, Vec<(usize, char, GXTError)>) {
+
+    let custom_table = options.custom_table;
+    let mut failures: Vec<(usize, char, GXTError)> = vec!();
+    let mut codes: Vec<u16> = vec!();
+
+    let string: String = match options.normalization {
+        None => string.to_string(),
+        Some(NormalizationForm::NFC) | Some(NormalizationForm::Auto) => string.nfc().collect(),
+        Some(NormalizationForm::NFD) => string.nfd().collect(),
+        Some(NormalizationForm::NFKC) => string.nfkc().collect(),
+        Some(NormalizationForm::NFKD) => string.nfkd().collect(),
+    };
+
+    let characters: Vec<char> = string.chars().collect();
+    let tag_mask = tag_span_mask(&characters, &'~');
+
+    for (i,e) in characters.into_iter().enumerate() {
+        // tags are always encoded using the default table, same as when they're decoded
+        let custom_table = if tag_mask[i] { &None } else { custom_table };
+
+        match encode_character_for_format(e, format, custom_table) {
+            Ok(c) => { codes.push(c); },
+            Err(x) => {
+                // in the "auto" mode, a precomposed character may still be encoded if the table
+                // has its decomposed form instead
+                if let Some(NormalizationForm::Auto) = options.normalization {
+                    let decomposed: Result<Vec<u16>,GXTError> = std::iter::once(e).nfd()
+                        .map(|d| encode_character_for_format(d, format, custom_table))
+                        .collect();
+                    if let Ok(d) = decomposed {
+                        codes.extend(d);
+                        continue;
+                    }
+                }
+
+                // unsupported characters are only replaced if the caller asked for it
+                let Some(replacement) = options.fallback.as_ref().and_then(|f| f.substitute(e)) else {
+                    failures.push((i, e, x));
+                    continue;
+                };
+                let replaced: Result<Vec<u16>,GXTError> = replacement.chars()
+                    .map(|r| encode_character_for_format(r, format, custom_table))
+                    .collect();
+                match replaced {
+                    Ok(r) => {
+                        codes.extend(r);
+                        substitutions.push(GXTSubstitution { position: i, original: e, replacement });
+                    },
+                    Err(x) => { failures.push((i, e, x)); },
+                }
+            },
+        }
+    }
+
+    (codes, failures)
+}
+
+fn encode_string(string: &str, format: &GXTFileFormat, options: &WriteOptions, substitutions: &mut Vec<GXTSubstitution>) -> Result<Vec<u8>,GXTError> {
+
+    let (codes, failures) = encode_codes(string, format, options, substitutions);
+    if let Some((_, _, x)) = failures.into_iter().next() {
+        return Err(x);
+    }
+    if codes.len() > MAX_STRING_LENGTH {
+        return Err(GXTError::TooLargeToRead { what: "characters in a string", limit: MAX_STRING_LENGTH, table: None, key: None });
+    }
 
     let mut res: Vec<u8> = vec!();
 
     match format {
         GXTFileFormat::San8 => {
-            for e in string.chars() {
-                let widechar = encode_character(e, format, custom_table)?;
-                if widechar >= 256 {
-                    return Err(GXTError::CompilationError(format!("Character U+{:04X} is to be encoded as {:04X}, but the 8-bit format GXT file can only encode characters below 255.",u32::from(e),widechar)));
-                }
-                res.push((widechar & 0xFF) as u8);
+            for c in codes {
+                res.push((c & 0xFF) as u8);
             }
             res.push(0); // null-terminator
         },
         GXTFileFormat::Three | GXTFileFormat::Vice | GXTFileFormat::San16 => {
-            for e in string.chars() {
-                let widechar: u16 = encode_character(e, format, custom_table)?;
-                res.extend_from_slice(&u16::to_le_bytes(widechar));
+            for c in codes {
+                res.extend_from_slice(&u16::to_le_bytes(c));
             }
             res.extend_from_slice(&[0,0]); //null-terminator
         },
@@ -295,6 +780,42 @@ fn encode_string(string: &str, format: &GXTFileFormat, custom_table: &Option<GXT
     Ok(res)
 }
 
+fn decode_string(codes: &[u16], format: &GXTFileFormat, custom_table: &Option<GXTCharacterTable>) -> String {
+
+    let tag_mask = tag_span_mask(codes, &TILDE_CODE);
+
+    codes.iter().zip(tag_mask).map(|(c, in_tag)| {
+        // tags are always written using the default table, no matter what the custom table says
+        decode_character(*c, format, if in_tag { &None } else { custom_table })
+    }).collect()
+}
+
+// the tilde has the same code in all of the default character tables. the games treat this code
+// as the start or end of a tag, no matter which character the font shows in its place
+const TILDE_CODE: u16 = 0x7E;
+
+/// marks every element that belongs to a tilde-delimited tag (including the tildes themselves).
+/// tildes are paired up from the start of the string, and a final unpaired tilde is treated as
+/// regular text
+fn tag_span_mask<T: PartialEq>(items: &[T], tilde: &T) -> Vec<bool> {
+
+    let mut mask = vec![false; items.len()];
+    let mut open_tilde: Option<usize> = None;
+
+    for (i, e) in items.iter().enumerate() {
+        if e != tilde { continue; }
+        match open_tilde {
+            None => { open_tilde = Some(i); },
+            Some(o) => {
+                mask[o..=i].fill(true);
+                open_tilde = None;
+            },
+        }
+    }
+
+    mask
+}
+
 /// Read a name list from a TOML file. The file is expected to contain a single array of strings,
 /// which is named "names".
 ///
@@ -311,7 +832,7 @@ pub fn read_name_list(file: &mut (impl Read + std::io::Seek + std::io::BufRead))
         names: Vec<String>
     }
         
-    let raw_table: NameList = toml::from_str(&raw_data)?;
+    let raw_table: NameList = parse_toml(&raw_data)?;
 
     let mut table: HashMap<u32,String> = Default::default();
 
@@ -337,7 +858,7 @@ pub fn read_custom_table(file: &mut (impl Read + std::io::Seek + std::io::BufRea
     let mut raw_data: String = Default::default();
     file.read_to_string(&mut raw_data)?;
         
-    let mut table: GXTCharacterTable = toml::from_str(&raw_data)?;
+    let mut table: GXTCharacterTable = parse_toml(&raw_data)?;
 
     // If there's no encode table, build one using the decode table
     if table.encode_table.len() == 0 {
@@ -349,6 +870,60 @@ pub fn read_custom_table(file: &mut (impl Read + std::io::Seek + std::io::BufRea
     return Ok(table);
 }
 
+/// Read a fallback policy from a TOML file.
+///
+/// The file may have a `strip_diacritics` boolean field, and a \[transliteration\] section that
+/// maps single characters to their replacements. See [GXTFallbackPolicy] for details.
+pub fn read_fallback_policy(file: &mut (impl Read + std::io::Seek)) -> Result<GXTFallbackPolicy,GXTError> {
+
+    let mut raw_data: String = Default::default();
+    file.read_to_string(&mut raw_data)?;
+
+    let policy: GXTFallbackPolicy = parse_toml(&raw_data)?;
+    Ok(policy)
+}
+
+/// Write a custom character table into a TOML file, in the same format that [read_custom_table]
+/// expects.
+///
+/// Entries are written sorted by their character codes. The \[encode_table\] section is only
+/// written if it can't be derived from the \[decode_table\], that is, if several codes decode into
+/// the same character or the encode table differs from the reverse of the decode table.
+pub fn write_custom_table(table: &GXTCharacterTable, file: &mut impl Write) -> Result<(),GXTError> {
+
+    let mut decode_entries: Vec<(&u16,&char)> = table.decode_table.iter().collect();
+    decode_entries.sort();
+
+    let mut out_string = String::from("[decode_table]\n");
+    for (k,v) in &decode_entries {
+        out_string += &format!("{} = {}\n", k, toml::Value::String(v.to_string()));
+    }
+
+    let mut derived_table: HashMap<char,u16> = Default::default();
+    let mut is_derivable = true;
+    for (k,v) in &decode_entries {
+        if derived_table.insert(**v, **k).is_some() {
+            is_derivable = false; // the same character is decoded from more than one code
+        }
+    }
+    if derived_table != table.encode_table {
+        is_derivable = false;
+    }
+
+    if !is_derivable {
+        let mut encode_entries: Vec<(&char,&u16)> = table.encode_table.iter().collect();
+        encode_entries.sort_by(|a,b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+
+        out_string += "\n[encode_table]\n";
+        for (k,v) in &encode_entries {
+            out_string += &format!("{} = {}\n", toml::Value::String(k.to_string()), v);
+        }
+    }
+
+    file.write_all(out_string.as_bytes())?;
+    Ok(())
+}
+
 #[derive(Clone)]
 struct GXTInternalTKEY {
     name: Option<[u8;8]>, //None for GTA 3 or MAIN block in VC
@@ -376,13 +951,28 @@ struct GXTInternalTABL {
     entries: Vec<GXTInternalTABLEntry>, //array of names and offsets
 }
 
+#[derive(Clone)]
 struct GXTCompilationTDAT {
     // this buffer will store the actual contents of TDAT. it will be gradually filled with new
     // strings
     buffer: Vec<u8>,
     // this hashmap will store offsets to each individual string and keep track of which ones
-    // already exist. the keys are string VALUES, not string names
-    offset_map: HashMap<String, usize>,
+    // already exist. the keys are string VALUES, not string names. the length of each string (in
+    // characters) and the characters replaced while encoding it are kept as well, since they
+    // apply to every key that reuses it
+    offset_map: HashMap<String, (usize, usize, Vec<GXTSubstitution>)>,
+    // the length of the table's strings, counting shared strings once for every key, as that's
+    // how much text reading the table back produces
+    total_length: usize,
+}
+
+// converts the size of (or an offset into) a section to the 32 bits a GXT file stores it in
+fn section_size(size: u64, section: &'static str, table_name: &str) -> Result<u32,GXTError> {
+    u32::try_from(size).map_err(|_| GXTError::SectionTooLarge {
+        section,
+        size,
+        table: Some(table_name.to_string()),
+    })
 }
 
 /// returns a sanitized string name from a raw 8-byte token name
@@ -390,16 +980,9 @@ fn string_from_name(name: &GXTStringName, name_list: &Option<HashMap<u32,String>
 
     match name {
         GXTStringName::Text(t) => {
-            // this complicated code's goal is to catch potential names that have zero bytes followed by
-            // nonzero bytes
-            let mut last_nonzero_index: Option<usize> = None;
-            
-            for i in 0..t.len() {
-                let c = t[i];
-                if (c != 0) && ((last_nonzero_index == None) || (i > last_nonzero_index.unwrap())) { 
-                    last_nonzero_index = Some(i); 
-                }
-            }
+            // the name ends at the last nonzero byte, so that names with zero bytes followed by
+            // nonzero bytes are caught
+            let last_nonzero_index: Option<usize> = t.iter().rposition(|c| *c != 0);
 
             match last_nonzero_index {
                 None => {return "".to_string();},
@@ -445,7 +1028,7 @@ fn string_to_name_basic(string: &str) -> Result<[u8;8],GXTError> {
     };
 
     if string.as_bytes().len() > 8 {
-        return Err(GXTError::CompilationError(format!("String name ({}) can't be longer than 8 bytes",string)));
+        return Err(GXTError::NameTooLong { name: string.to_string(), table: None });
     }
     let len = string.as_bytes().len();
 
@@ -455,11 +1038,10 @@ fn string_to_name_basic(string: &str) -> Result<[u8;8],GXTError> {
 
 fn string_to_name_crc32(string: &str) -> Result<u32,GXTError> {
     // if the string resembles a CRC32, read the hexadecimal value!
-    if (string.chars().count() >= 2) // if the string is at least two characters long
-        && (string.chars().nth(0).unwrap() == '#') // and the first character is a # sign
-        && (string.chars().nth(1).unwrap() != '#') // and the second character ISN'T a # sign
+    if string.starts_with('#') // if the first character is a # sign
+        && !string.starts_with("##") // and the second character ISN'T a # sign
         && (string.chars().count() == 9) { //and it's exactly 9 characters long, read it as a hash
-        if !string.is_ascii() { return Err(GXTError::CompilationError(format!("Invalid characters in hash-based string ({})",string))); }
+        if !string.is_ascii() { return Err(GXTError::InvalidHash { name: string.to_string(), table: None }); }
         let mut hex_hash: [u8; 8] = [0;8];
         hex_hash[0..8].copy_from_slice(&string.as_bytes()[1..9]);
         let mut raw_hash: [u8; 4] = [0;4];
@@ -468,7 +1050,7 @@ fn string_to_name_crc32(string: &str) -> Result<u32,GXTError> {
                 raw_hash[0..4].copy_from_slice(&v.as_slice()[0..4]);
             },
             Err(_e) => { 
-                return Err(GXTError::CompilationError(format!("Hash-based string ({}) does not contain a valid hex value",string)));
+                return Err(GXTError::InvalidHash { name: string.to_string(), table: None });
             }
         };
         let hash: u32 = u32::from_be_bytes(raw_hash);
@@ -494,13 +1076,24 @@ fn string_to_name(string: &str, format: &GXTFileFormat) -> Result<GXTStringName,
     }
 }
 
-fn gxt_read_tabl(file: &mut (impl Read + std::io::Seek)) -> Result<GXTInternalTABL,GXTError> {
+// returns the name of a table for use in error messages, given its raw name from TKEY
+fn table_name_of(name: &Option<[u8;8]>) -> String {
+    match name {
+        None => MAIN_TABLE_NAME.to_string(),
+        Some(n) => string_from_name(&GXTStringName::Text(*n), &None),
+    }
+}
+
+fn gxt_read_tabl(file: &mut (impl Read + std::io::Seek), file_length: u64) -> Result<GXTInternalTABL,GXTError> {
+
+    let tabl_offset = file.stream_position()?;
+    let at = |e| truncated(e, "TABL", tabl_offset, &None, None);
 
     let mut magic_number: [u8; 4] = [0;4];
-    file.read_exact(&mut magic_number)?;
+    file.read_exact(&mut magic_number).map_err(at)?;
     
     if magic_number != *b"TABL" {
-        return Err(GXTError::ParsingError("Invalid TABL header".to_string()));
+        return Err(GXTError::InvalidHeader { section: "TABL", offset: tabl_offset, table: None });
     }
 
     let mut tabl = GXTInternalTABL {
@@ -509,20 +1102,31 @@ fn gxt_read_tabl(file: &mut (impl Read + std::io::Seek)) -> Result<GXTInternalTA
     };
     
     let mut raw_size: [u8; 4] = [0;4];
-    file.read_exact(&mut raw_size)?;
+    file.read_exact(&mut raw_size).map_err(at)?;
 
     tabl.size = u32::from_le_bytes(raw_size);
+    if tabl_offset + 8 + u64::from(tabl.size) > file_length {
+        return Err(GXTError::UnexpectedEnd { section: "TABL", offset: tabl_offset, table: None, key: None });
+    }
+
     let count = u32::from_le_bytes(raw_size) / 12; //each TABL entry is 12 bytes long
+    if count as usize > MAX_TABLES {
+        return Err(GXTError::TooLarge { what: "tables", limit: MAX_TABLES, table: None });
+    }
     let mut index: u32 = 0;
     
     while index < count {
         let mut raw_name: [u8; 8] = [0;8];
         let mut raw_offset: [u8; 4] = [0;4];
         
-        file.read_exact(&mut raw_name)?;
-        file.read_exact(&mut raw_offset)?;
+        file.read_exact(&mut raw_name).map_err(at)?;
+        file.read_exact(&mut raw_offset).map_err(at)?;
 
         let offset = u32::from_le_bytes(raw_offset);
+        if u64::from(offset) >= file_length {
+            let name = string_from_name(&GXTStringName::Text(raw_name), &None);
+            return Err(GXTError::OutOfBounds { section: "TABL", offset: tabl_offset, table: Some(name), key: None });
+        }
 
         tabl.entries.push(GXTInternalTABLEntry { name:raw_name, offset:offset, is_main: (index == 0) && (raw_name == *b"MAIN\0\0\0\0") });
 
@@ -533,25 +1137,29 @@ fn gxt_read_tabl(file: &mut (impl Read + std::io::Seek)) -> Result<GXTInternalTA
 
 }
 
-fn gxt_read_tkey(file: &mut (impl Read + std::io::Seek), format: &GXTFileFormat, name: Option<[u8;8]>, offset:Option<u32>, ordering: &Option<ImportOrdering>) -> Result<GXTInternalTKEY,GXTError> {
+fn gxt_read_tkey(file: &mut (impl Read + std::io::Seek), file_length: u64, format: &GXTFileFormat, name: Option<[u8;8]>, offset:Option<u32>, ordering: &Option<ImportOrdering>, strict: bool) -> Result<GXTInternalTKEY,GXTError> {
     //name should be None for GTA3 and VC's MAIN entry
 
-    file.seek(std::io::SeekFrom::Start(offset.unwrap_or(0).into()))?;
+    let tkey_offset: u64 = offset.unwrap_or(0).into();
+    let table_name = Some(table_name_of(&name));
+    let at = |e| truncated(e, "TKEY", tkey_offset, &table_name, None);
+
+    file.seek(std::io::SeekFrom::Start(tkey_offset))?;
 
     let actual_name: Option<[u8;8]> = match name {
         None => None,
         Some(_) => {
             let mut raw_name: [u8;8] = [0;8];
-            file.read_exact(&mut raw_name)?;
+            file.read_exact(&mut raw_name).map_err(at)?;
             Some(raw_name)
         },
     };
     
     let mut magic_number: [u8; 4] = [0;4];
-    file.read_exact(&mut magic_number)?;
+    file.read_exact(&mut magic_number).map_err(at)?;
     
     if magic_number != *b"TKEY" {
-        return Err(GXTError::ParsingError("Invalid TKEY header".to_string()));
+        return Err(GXTError::InvalidHeader { section: "TKEY", offset: file.stream_position()? - 4, table: table_name });
     }
 
     let mut tkey = GXTInternalTKEY {
@@ -562,32 +1170,39 @@ fn gxt_read_tkey(file: &mut (impl Read + std::io::Seek), format: &GXTFileFormat,
     };
 
     let mut raw_size: [u8; 4] = [0;4];
-    file.read_exact(&mut raw_size)?;
+    file.read_exact(&mut raw_size).map_err(at)?;
 
     tkey.size = u32::from_le_bytes(raw_size);
+    // the size decides how many entries are read, so it must not go past the end of the file
+    if file.stream_position()? + u64::from(tkey.size) > file_length {
+        return Err(GXTError::UnexpectedEnd { section: "TKEY", offset: tkey_offset, table: table_name, key: None });
+    }
     
     let entry_size = match format {
         GXTFileFormat::Three | GXTFileFormat::Vice => 12, //4 for offset, 8 for name
         GXTFileFormat::San8 | GXTFileFormat::San16 => 8, //4 for offset, 4 for CRC32
     };
     let count = u32::from_le_bytes(raw_size) / entry_size; //each TKEY entry is 12 bytes long
+    if count as usize > MAX_ENTRIES {
+        return Err(GXTError::TooLarge { what: "strings", limit: MAX_ENTRIES, table: table_name });
+    }
     let mut index: u32 = 0;
 
     while index < count {
         
         let mut raw_offset: [u8; 4] = [0;4];
-        file.read_exact(&mut raw_offset)?;
+        file.read_exact(&mut raw_offset).map_err(at)?;
         let offset = u32::from_le_bytes(raw_offset);
         
         let name: GXTStringName = match format {
             GXTFileFormat::Three | GXTFileFormat::Vice => {
                 let mut raw_name: [u8; 8] = [0;8];
-                file.read_exact(&mut raw_name)?;
+                file.read_exact(&mut raw_name).map_err(at)?;
                 GXTStringName::Text(raw_name)
             },
             GXTFileFormat::San8 | GXTFileFormat::San16 => {
                 let mut raw_crc32: [u8; 4] = [0;4];
-                file.read_exact(&mut raw_crc32)?;
+                file.read_exact(&mut raw_crc32).map_err(at)?;
                 GXTStringName::CRC32(u32::from_le_bytes(raw_crc32))
             },
         };
@@ -596,6 +1211,11 @@ fn gxt_read_tkey(file: &mut (impl Read + std::io::Seek), format: &GXTFileFormat,
 
         index += 1;
     }
+
+    // the games binary-search TKEY, so entries out of order may not be found
+    if strict && !tkey.entries.is_sorted_by(|a,b| a.name <= b.name) {
+        return Err(GXTError::UnsortedEntries { section: "TKEY", offset: tkey_offset, table: table_name });
+    }
     
     match ordering {
         None | Some(ImportOrdering::Native) => {},
@@ -611,7 +1231,50 @@ fn gxt_read_tkey(file: &mut (impl Read + std::io::Seek), format: &GXTFileFormat,
     return Ok(tkey);
 }
 
-fn gxt_read_tdat(file: &mut (impl Read + std::io::Seek), tkey: &GXTInternalTKEY, tkey_offset: Option<u32>, format: &GXTFileFormat, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32, String>>) -> Result<IndexMap<String,String>,GXTError> {
+/// The maximum length of a string read from a GXT file, in characters. No game has a buffer
+/// anywhere near this long, so a longer string can only come from a corrupt file.
+pub const MAX_STRING_LENGTH: usize = 0x10000;
+
+/// The maximum number of tables read from a GXT file, including the main table.
+pub const MAX_TABLES: usize = 0x1000;
+
+/// The maximum number of strings read from a GXT file, in all of its tables together.
+pub const MAX_ENTRIES: usize = 0x40000;
+
+/// The maximum length of all the strings read from a GXT file together, in characters. Strings
+/// that share their data are counted once for every name they have, as each name gets its own copy.
+pub const MAX_TOTAL_LENGTH: usize = 0x1000000;
+
+// the result of looking for a string in TDAT
+enum GXTStringScan {
+    Complete(Vec<u16>),
+    Unterminated,
+    TooLong,
+}
+
+// reads the character codes of a null-terminated string starting at an offset in TDAT
+fn read_string_codes(tdat: &[u8], offset: usize, format: &GXTFileFormat) -> GXTStringScan {
+
+    let data = tdat.get(offset..).unwrap_or_default();
+    let mut codes: Vec<u16> = vec!();
+
+    let mut values: Box<dyn Iterator<Item = u16>> = match format {
+        GXTFileFormat::Three | GXTFileFormat::Vice => Box::new(data.chunks_exact(2).map(|c| c[0] as u16 + 256*(c[1] as u16))),
+        GXTFileFormat::San8 => Box::new(data.iter().map(|b| *b as u16)),
+        GXTFileFormat::San16 => Box::new(data.chunks_exact(2).map(|c| c[0] as u16)),
+    };
+
+    loop {
+        match values.next() {
+            None => { return GXTStringScan::Unterminated; },
+            Some(0) => { return GXTStringScan::Complete(codes); },
+            Some(_) if codes.len() >= MAX_STRING_LENGTH => { return GXTStringScan::TooLong; },
+            Some(c) => { codes.push(c); },
+        }
+    }
+}
+
+fn gxt_read_tdat(file: &mut (impl Read + std::io::Seek), file_length: u64, tkey: &GXTInternalTKEY, format: &GXTFileFormat, ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32, String>>) -> Result<IndexMap<String,String>,GXTError> {
     
     let mut tkey_data_sorted = tkey.entries.clone();
     tkey_data_sorted.sort_by(|a,b| a.offset.cmp(&b.offset));
@@ -619,63 +1282,70 @@ fn gxt_read_tdat(file: &mut (impl Read + std::io::Seek), tkey: &GXTInternalTKEY,
     let mut key_ordering:  Vec<String> = Vec::new();
     let mut offset_ordering: Vec<String> = Vec::new();
 
-    let tdat_offset = tkey_offset.unwrap_or(0) + tkey.size + 8 + match tkey.name {
+    // computed in 64 bits, as a crafted file may have offsets and sizes that overflow 32 bits
+    let tdat_offset: u64 = u64::from(tkey.offset) + u64::from(tkey.size) + 8 + match tkey.name {
         None => 0, //MAIN block doesn't have the extra 8 bytes at the start
         Some(_) => 8}; //named blocks do
 
-    file.seek(std::io::SeekFrom::Start(tdat_offset.into()))?;
+    let table_name = Some(table_name_of(&tkey.name));
+    let at = |e| truncated(e, "TDAT", tdat_offset, &table_name, None);
+
+    file.seek(std::io::SeekFrom::Start(tdat_offset))?;
 
     let mut magic_number: [u8; 4] = [0;4];
-    file.read_exact(&mut magic_number)?;
+    file.read_exact(&mut magic_number).map_err(at)?;
     
     if magic_number != *b"TDAT" {
-        return Err(GXTError::ParsingError("Invalid TDAT header".to_string()));
+        return Err(GXTError::InvalidHeader { section: "TDAT", offset: tdat_offset, table: table_name });
     }
 
     let mut raw_size: [u8; 4] = [0;4];
-    file.read_exact(&mut raw_size)?;
+    file.read_exact(&mut raw_size).map_err(at)?;
+
+    // the whole TDAT is read at once. if the file ends before the declared size, only the part
+    // that exists is read, and the strings that don't fit into it are reported
+    let tdat_size = u64::from(u32::from_le_bytes(raw_size));
+    let available = tdat_size.min(file_length.saturating_sub(tdat_offset + 8));
+    let mut tdat: Vec<u8> = vec!(0; available as usize);
+    file.read_exact(&mut tdat).map_err(at)?;
+    let is_truncated = available < tdat_size;
 
     let mut table = IndexMap::<String,String>::new();
     let mut offset_table = HashMap::<String,u64>::new();
 
+    // many entries may point at the same string, which is only decoded once. every entry still
+    // gets its own copy, so their total length is limited
+    let mut decoded = HashMap::<u32,String>::new();
+    let mut total_length: usize = 0;
+
     for e in &tkey.entries {
         let name = string_from_name(&e.name, name_list);
-        let offset: u64 = (tdat_offset + 8 + e.offset).into();
-        //eprintln!("Entry offset for {name} is {}, seeking to {offset}...", e.offset);
-        
-        file.seek(std::io::SeekFrom::Start(offset))?;
-                
-        let mut value = String::new();
-
-        match format {
-            GXTFileFormat::Three | GXTFileFormat::Vice => {
-                let mut raw_2byte_sequence: [u8; 2] = [0;2];
-
-                loop {
-                    file.read_exact(&mut raw_2byte_sequence)?;
-                    let character_value = raw_2byte_sequence[0] as u16 + 256*(raw_2byte_sequence[1] as u16);
-                    if character_value == 0 { break; }
-                    value.push(decode_character(character_value,&format,custom_table));
-                };
-            },
-            GXTFileFormat::San8 => {
-                let mut raw_byte: [u8; 1] = [0];
-                loop {
-                    file.read_exact(&mut raw_byte)?;
-                    if raw_byte[0] == 0 { break; }
-                    value.push(decode_character(raw_byte[0].into(),&format,custom_table));
-                };
-            },
-            GXTFileFormat::San16 => {
-                let mut raw_2byte_sequence: [u8; 2] = [0;2];
-
-                loop {
-                    file.read_exact(&mut raw_2byte_sequence)?;
-                    let character_value = raw_2byte_sequence[0] as u16;
-                    if character_value == 0 { break; }
-                    value.push(decode_character(character_value,&format,custom_table));
+        let offset: u64 = tdat_offset + 8 + u64::from(e.offset);
+
+        let value = match decoded.get(&e.offset) {
+            Some(v) => v.clone(),
+            None => {
+                let codes = match read_string_codes(&tdat, e.offset as usize, format) {
+                    GXTStringScan::Complete(codes) => codes,
+                    GXTStringScan::Unterminated if is_truncated => {
+                        return Err(GXTError::UnexpectedEnd { section: "TDAT", offset, table: table_name, key: Some(name) });
+                    },
+                    GXTStringScan::Unterminated => {
+                        return Err(GXTError::OutOfBounds { section: "TDAT", offset, table: table_name, key: Some(name) });
+                    },
+                    GXTStringScan::TooLong => {
+                        return Err(GXTError::StringTooLong { offset, table: table_name, key: Some(name) });
+                    },
                 };
+                let value = decode_string(&codes, format, custom_table);
+                decoded.insert(e.offset, value.clone());
+                value
             },
+        };
+
+        total_length += value.chars().count();
+        if total_length > MAX_TOTAL_LENGTH {
+            return Err(GXTError::TooLarge { what: "characters of text", limit: MAX_TOTAL_LENGTH, table: table_name });
         }
         
         let name_c1 = name.clone();
@@ -719,6 +1389,13 @@ impl GXTFile {
         }
     }
 
+    /// Returns an iterator over all the tables in the file, starting with the main table (named
+    /// [MAIN_TABLE_NAME]), followed by the auxiliary tables.
+    pub fn tables(&self) -> impl Iterator<Item = (&str, &IndexMap<String,String>)> {
+        std::iter::once((MAIN_TABLE_NAME, &self.main_table))
+            .chain(self.aux_tables.iter().map(|(k,v)| (k.as_str(), v)))
+    }
+
     /// Write this GXTFile's contents as a TOML file.
     pub fn write_to_text (&self, file: &mut impl Write) -> Result<(),GXTError> {
 
@@ -734,15 +1411,16 @@ impl GXTFile {
         let mut raw_data: String = Default::default();
         file.read_to_string(&mut raw_data)?;
         
-        let file: GXTFile = toml::from_str(&raw_data)?;
+        let file: GXTFile = parse_toml(&raw_data)?;
         return Ok(file);
     }
 
-    fn create_tkey(&self, table: &IndexMap<String,String>, table_name: Option<&str>, custom_table: &Option<GXTCharacterTable>) -> Result<(GXTInternalTKEY,GXTCompilationTDAT), GXTError> {
+    fn create_tkey(&self, table: &IndexMap<String,String>, table_name: Option<&str>, options: &WriteOptions, report: &mut GXTWriteReport) -> Result<(GXTInternalTKEY,GXTCompilationTDAT), GXTError> {
 
         let mut tdat = GXTCompilationTDAT {
             buffer: vec!(),
             offset_map: Default::default(),
+            total_length: 0,
         };
 
         let mut tkey = GXTInternalTKEY {
@@ -755,31 +1433,52 @@ impl GXTFile {
             entries: vec!(),
         };
 
+        let location = table_name.unwrap_or(MAIN_TABLE_NAME);
+
+        // the games binary-search TKEY, so two entries with the same name (or hash) would make
+        // one of the strings impossible to find
+        let mut names: HashMap<GXTStringName, &String> = HashMap::new();
+
         for (k,v) in table {
+            let name = string_to_name(k,&self.format).map_err(|e| e.with_location(location, None))?;
+            if let Some(other) = names.insert(name.clone(), k) {
+                return Err(GXTError::DuplicateName { key: k.clone(), other: other.clone(), table: Some(location.to_string()) });
+            }
+
             let offset = tdat.offset_map.get(v);
-            match offset {
-                Some(o) => {
+            let (offset, length, substitutions) = match offset {
+                Some((o, length, substitutions)) => {
                     // String exists, we reuse the existing offset
-                    tkey.entries.push( GXTInternalTKEYEntry {
-                        name: string_to_name(k,&self.format)?,
-                        offset: *o as u32,
-                    });
+                    (*o, *length, substitutions.clone())
                 },
                 None => {
                     // String does not exist, we add a new one
                     let cur_pos: usize = tdat.buffer.len();
-                    let _ = tdat.buffer.write(&encode_string(v,&self.format,custom_table)?);
-                    
-                    tkey.entries.push( GXTInternalTKEYEntry {
-                        name: string_to_name(k,&self.format)?,
-                        offset: cur_pos as u32,
-                    });
+                    let mut substitutions: Vec<GXTSubstitution> = vec!();
+                    let encoded = encode_string(v,&self.format,options,&mut substitutions).map_err(|e| e.with_location(location, Some(k)))?;
+                    let length = match self.format {
+                        GXTFileFormat::San8 => encoded.len() - 1,
+                        _ => encoded.len() / 2 - 1,
+                    };
+                    let _ = tdat.buffer.write(&encoded);
+                    tdat.offset_map.insert(v.clone(), (cur_pos, length, substitutions.clone()));
+                    (cur_pos, length, substitutions)
                 },
             };
-            tkey.size += match self.format {
+            tdat.total_length += length;
+            if !substitutions.is_empty() {
+                report.substitutions.entry(location.to_string()).or_default()
+                    .insert(k.clone(), substitutions);
+            }
+            tkey.entries.push( GXTInternalTKEYEntry {
+                name,
+                offset: section_size(offset as u64, "TDAT", location)?,
+            });
+            let entry_size: u64 = match self.format {
                 GXTFileFormat::Three | GXTFileFormat::Vice => 12, //4 for offset, 8 for name
                 GXTFileFormat::San8 | GXTFileFormat::San16 => 8, //4 for offset, 4 for CRC32
             };
+            tkey.size = section_size(u64::from(tkey.size) + entry_size, "TKEY", location)?;
         }
         match self.format {
             GXTFileFormat::San8 | GXTFileFormat::San16 => {
@@ -787,9 +1486,7 @@ impl GXTFile {
                 // gets aligned across a 4-byte boundary -- in practice, this just means that
                 // each TDAT's length must be padded until it can divide by 4, because all the
                 // other blocks already have length divisible by 4
-                let filler: u32 = if (tdat.buffer.len() % 4) != 0 {
-                    4 - (u32::try_from(tdat.buffer.len()).unwrap() % 4)
-                } else { 0 };
+                let filler: usize = (4 - tdat.buffer.len() % 4) % 4;
 
                 for _ in 0..filler {
                     tdat.buffer.push(0);
@@ -797,6 +1494,7 @@ impl GXTFile {
             },
             _ => {},
         };
+        section_size(tdat.buffer.len() as u64, "TDAT", location)?;
         Ok((tkey,tdat))
     }
     fn write_tkey_to_gxt(&self, file: &mut impl Write, tkey: &GXTInternalTKEY) -> Result<(), GXTError> {
@@ -820,13 +1518,13 @@ impl GXTFile {
                 GXTFileFormat::Three | GXTFileFormat::Vice => {
                     match e.name {
                         GXTStringName::Text(t) => { file.write(&t)?; },
-                        GXTStringName::CRC32(_) => { return Err(GXTError::CompilationError("File of this format cannot have CRC32-based string names".to_string())); },
+                        GXTStringName::CRC32(_) => { return Err(GXTError::InternalError("File of this format cannot have CRC32-based string names".to_string())); },
                     }
                 },
                 GXTFileFormat::San8 | GXTFileFormat::San16 => {
                     match e.name {
                         GXTStringName::CRC32(h) => { file.write(&u32::to_le_bytes(h))?; },
-                        GXTStringName::Text(_) => { return Err(GXTError::CompilationError("File of this format cannot have text-based string names".to_string())); }, // this is not an error the end user should see, as text-based names are converted to CRC32 when exporting an SA format GXT
+                        GXTStringName::Text(_) => { return Err(GXTError::InternalError("File of this format cannot have text-based string names".to_string())); }, // this is not an error the end user should see, as text-based names are converted to CRC32 when exporting an SA format GXT
                     }
                 },
             }
@@ -843,108 +1541,136 @@ impl GXTFile {
     /// is inadequate. This is useful for non-EFIGS versions of the game.
     pub fn write_to_gxt (&self, file: &mut impl Write, custom_table: &Option<GXTCharacterTable>) -> Result<(), GXTError> {
 
-        let (main_tkey,main_tdat) = self.create_tkey(&self.main_table, None, custom_table)?;
+        let options = WriteOptions {
+            custom_table,
+            ..Default::default()
+        };
+        self.write_to_gxt_with_options(file, &options)?;
+        Ok(())
+    }
+
+    /// Write this GXTFile's contents as an actual GXT file, using the specified [WriteOptions].
+    ///
+    /// This works the same as [GXTFile::write_to_gxt], but allows changing how the file is
+    /// compiled (for example, by replacing characters that can't be encoded). On success, a
+    /// [GXTWriteReport] is returned, describing any changes made to the strings along the way.
+    pub fn write_to_gxt_with_options (&self, file: &mut impl Write, options: &WriteOptions) -> Result<GXTWriteReport, GXTError> {
+
+        let mut report = GXTWriteReport::default();
+
+        // the reader refuses files over these limits, so such a file is never written
+        if 1 + self.aux_tables.len() > MAX_TABLES {
+            return Err(GXTError::TooLargeToRead { what: "tables", limit: MAX_TABLES, table: None, key: None });
+        }
+        let mut string_count: usize = 0;
+        let mut total_length: usize = 0;
+        let mut check_limits = |table: &IndexMap<String,String>, tdat: &GXTCompilationTDAT, name: &str| {
+            string_count += table.len();
+            total_length += tdat.total_length;
+            let exceeded = match (string_count > MAX_ENTRIES, total_length > MAX_TOTAL_LENGTH) {
+                (true, _) => Some(("strings", MAX_ENTRIES)),
+                (false, true) => Some(("characters of text", MAX_TOTAL_LENGTH)),
+                (false, false) => None,
+            };
+            match exceeded {
+                Some((what, limit)) => Err(GXTError::TooLargeToRead { what, limit, table: Some(name.to_string()), key: None }),
+                None => Ok(()),
+            }
+        };
+
+        let (main_tkey,main_tdat) = self.create_tkey(&self.main_table, None, options, &mut report)?;
+        check_limits(&self.main_table, &main_tdat, MAIN_TABLE_NAME)?;
 
         let mut aux_data: Vec<(GXTInternalTKEY,GXTCompilationTDAT)> = vec!();
 
         for (k,v) in &self.aux_tables {
-            aux_data.push(self.create_tkey(&v, Some(k), custom_table)?);
+            let data = self.create_tkey(v, Some(k), options, &mut report)?;
+            check_limits(v, &data.1, k)?;
+            aux_data.push(data);
         }
 
-        match self.format {
+        // the games binary-search the tables that follow MAIN by name, so they must be sorted
+        aux_data.sort_by_key(|a| a.0.name);
+
+        self.write_compiled_to_gxt(file, &(main_tkey,main_tdat), &aux_data)?;
+        Ok(report)
+    }
+
+    // writes the compiled tables as a GXT file. the table offsets are all computed before
+    // anything is written, so a file that's too large to address is never partially written
+    fn write_compiled_to_gxt(&self, file: &mut impl Write, main_data: &(GXTInternalTKEY,GXTCompilationTDAT), aux_data: &[(GXTInternalTKEY,GXTCompilationTDAT)]) -> Result<(), GXTError> {
+
+        let (main_tkey, main_tdat) = main_data;
+
+        let header_size: u64 = match self.format {
             GXTFileFormat::Three => {
-                if aux_data.len() > 0 {
-                    return Err(GXTError::CompilationError("A GTA III format file cannot have auxiliary tables".to_string()));
+                if !aux_data.is_empty() {
+                    return Err(GXTError::AuxTablesNotAllowed);
                 }
-                self.write_tkey_to_gxt(file,&main_tkey)?;
-                file.write(b"TDAT")?;
-                file.write(&u32::to_le_bytes(main_tdat.buffer.len().try_into().unwrap()))?;
-                file.write(&main_tdat.buffer)?;
-                Ok(())
-            },
-            GXTFileFormat::Vice => {
-                file.write(b"TABL")?;
-                let tabl_size: u32 = 12u32 * (1 + u32::try_from(self.aux_tables.len()).unwrap());
-                file.write(&u32::to_le_bytes( tabl_size ))?;
-
-                let mut table_offset = tabl_size + 8;
-                file.write(b"MAIN\0\0\0\0")?;
-                file.write(&u32::to_le_bytes( table_offset ))?;
-                table_offset += 8 + main_tkey.size + 8 + u32::try_from(main_tdat.buffer.len()).unwrap();
-
-                for e in &aux_data {
-                    match e.0.name {
-                        Some(n) => {
-                            let table_name: [u8;8] = n;
-                            file.write(&table_name)?;
-                            file.write(&u32::to_le_bytes( table_offset ))?;
-                        },
-                        None => {
-                            return Err(GXTError::CompilationError("Auxiliary tables must have a name".to_string()));
-                        },
-                    }
-                    table_offset += 16 + e.0.size + 8 + u32::try_from(e.1.buffer.len()).unwrap();
-                }
-                
-                self.write_tkey_to_gxt(file,&main_tkey)?;
-                file.write(b"TDAT")?;
-                file.write(&u32::to_le_bytes(main_tdat.buffer.len().try_into().unwrap()))?;
-                file.write(&main_tdat.buffer)?;
-                
-                for e in &aux_data {
-                    self.write_tkey_to_gxt(file,&e.0)?;
-                    file.write(b"TDAT")?;
-                    file.write(&u32::to_le_bytes(e.1.buffer.len().try_into().unwrap()))?;
-                    file.write(&e.1.buffer)?;
-                }
-                Ok(())
+                self.write_tkey_to_gxt(file,main_tkey)?;
+                file.write_all(b"TDAT")?;
+                file.write_all(&u32::to_le_bytes(section_size(main_tdat.buffer.len() as u64, "TDAT", MAIN_TABLE_NAME)?))?;
+                file.write_all(&main_tdat.buffer)?;
+                return Ok(());
             },
+            GXTFileFormat::Vice => 0,
+            GXTFileFormat::San8 | GXTFileFormat::San16 => 4, //version and character size
+        };
+
+        let tabl_size: u32 = section_size(12 * (1 + aux_data.len() as u64), "TABL", MAIN_TABLE_NAME)?;
+
+        // MAIN is written first, with the other tables following it
+        let mut table_offsets: Vec<u32> = vec!();
+        let mut table_offset: u64 = header_size + 8 + u64::from(tabl_size);
+        for (i, e) in std::iter::once(main_data).chain(aux_data).enumerate() {
+            table_offsets.push(u32::try_from(table_offset).map_err(|_| GXTError::OffsetTooLarge {
+                offset: table_offset,
+                table: Some(table_name_of(&e.0.name)),
+            })?);
+            table_offset += match i {
+                0 => 8, //MAIN block doesn't have the extra 8 bytes at the start
+                _ => 16, //named blocks do
+            } + u64::from(e.0.size) + 8 + e.1.buffer.len() as u64;
+        }
+
+        match self.format {
             GXTFileFormat::San8 | GXTFileFormat::San16 => {
-                file.write(&u16::to_le_bytes(4))?;
-                file.write(&u16::to_le_bytes( match self.format {
+                file.write_all(&u16::to_le_bytes(4))?;
+                file.write_all(&u16::to_le_bytes( match self.format {
                     GXTFileFormat::San8 => 8,
                     GXTFileFormat::San16 => 16,
-                    _ => { return Err(GXTError::CompilationError("This GTA SA format is somehow not a GTA SA format?".to_string())); }
+                    _ => { return Err(GXTError::InternalError("This GTA SA format is somehow not a GTA SA format?".to_string())); }
                 }))?;
-
-                file.write(b"TABL")?;
-                let tabl_size: u32 = 12u32 * (1 + u32::try_from(self.aux_tables.len()).unwrap());
-                file.write(&u32::to_le_bytes( tabl_size ))?;
-
-                let mut table_offset = 4 + tabl_size + 8;
-                file.write(b"MAIN\0\0\0\0")?;
-                file.write(&u32::to_le_bytes( table_offset ))?;
-                table_offset += 8 + main_tkey.size + 8 + u32::try_from(main_tdat.buffer.len()).unwrap();
-
-                for e in &aux_data {
-                    match e.0.name {
-                        Some(n) => {
-                            let table_name: [u8;8] = n;
-                            file.write(&table_name)?;
-                            file.write(&u32::to_le_bytes( table_offset ))?;
-                        },
-                        None => {
-                            return Err(GXTError::CompilationError("Auxiliary tables must have a name".to_string()));
-                        },
-                    }
-                    table_offset += 16 + e.0.size + 8 + u32::try_from(e.1.buffer.len()).unwrap();
-                }
-                
-                self.write_tkey_to_gxt(file,&main_tkey)?;
-                file.write(b"TDAT")?;
-                file.write(&u32::to_le_bytes(main_tdat.buffer.len().try_into().unwrap()))?;
-                file.write(&main_tdat.buffer)?;
-                
-                for e in &aux_data {
-                    self.write_tkey_to_gxt(file,&e.0)?;
-                    file.write(b"TDAT")?;
-                    file.write(&u32::to_le_bytes(e.1.buffer.len().try_into().unwrap()))?;
-                    file.write(&e.1.buffer)?;
-                }
-                Ok(())
             },
+            _ => {},
+        }
+
+        file.write_all(b"TABL")?;
+        file.write_all(&u32::to_le_bytes( tabl_size ))?;
+
+        file.write_all(b"MAIN\0\0\0\0")?;
+        file.write_all(&u32::to_le_bytes( table_offsets[0] ))?;
+
+        for (e, offset) in aux_data.iter().zip(&table_offsets[1..]) {
+            match e.0.name {
+                Some(n) => {
+                    let table_name: [u8;8] = n;
+                    file.write_all(&table_name)?;
+                    file.write_all(&u32::to_le_bytes( *offset ))?;
+                },
+                None => {
+                    return Err(GXTError::InternalError("Auxiliary tables must have a name".to_string()));
+                },
+            }
         }
 
+        for e in std::iter::once(main_data).chain(aux_data) {
+            self.write_tkey_to_gxt(file,&e.0)?;
+            file.write_all(b"TDAT")?;
+            file.write_all(&u32::to_le_bytes(section_size(e.1.buffer.len() as u64, "TDAT", &table_name_of(&e.0.name))?))?;
+            file.write_all(&e.1.buffer)?;
+        }
+        Ok(())
     }
 
     /// Create a new GXTFile structure from the contents of a GXT file.
@@ -963,6 +1689,21 @@ impl GXTFile {
     /// string.
     ///
     pub fn read_from_gxt (file: &mut (impl Read + std::io::Seek), ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32, String>>) -> Result<GXTFile,GXTError> {
+        GXTFile::read_gxt(file, ordering, custom_table, name_list, false)
+    }
+
+    /// Create a new GXTFile structure from the contents of a GXT file, same as
+    /// [GXTFile::read_from_gxt], but also make sure that the file's tables and strings are sorted
+    /// the way the games expect.
+    ///
+    /// The games look up the tables that follow MAIN in TABL, as well as the strings in each TKEY,
+    /// using a binary search, so a file that isn't sorted may have tables or strings that can't be
+    /// found in-game. In that case, [GXTError::UnsortedEntries] is returned.
+    pub fn read_from_gxt_strict (file: &mut (impl Read + std::io::Seek), ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32, String>>) -> Result<GXTFile,GXTError> {
+        GXTFile::read_gxt(file, ordering, custom_table, name_list, true)
+    }
+
+    fn read_gxt (file: &mut (impl Read + std::io::Seek), ordering: &Option<ImportOrdering>, custom_table: &Option<GXTCharacterTable>, name_list: &Option<HashMap<u32, String>>, strict: bool) -> Result<GXTFile,GXTError> {
         
         let mut first_four_bytes: [u8; 4] = [0;4];
         file.read_exact(&mut first_four_bytes)?;
@@ -976,15 +1717,17 @@ impl GXTFile {
         } else if first_four_bytes == *b"\x04\0\x10\0" { //SA, 16-bit characters
             GXTFileFormat::San16
         } else { 
-            return Err(GXTError::ParsingError("This GXT file does not match any known GTA 3 / VC / SA format.".to_string()));
+            return Err(GXTError::UnknownFormat);
         };
+        // every offset and size in the file is checked against its length
+        let file_length = file.seek(std::io::SeekFrom::End(0))?;
         file.seek(std::io::SeekFrom::Start(0))?; //seek back to the start
 
         match format {
             GXTFileFormat::Three => {
-                let tkey = gxt_read_tkey(file,&format,None,None,&ordering)?;
+                let tkey = gxt_read_tkey(file,file_length,&format,None,None,ordering,strict)?;
                 return Ok(GXTFile {
-                    main_table: {gxt_read_tdat(file, &tkey, None, &format, &ordering, custom_table, name_list)?},
+                    main_table: {gxt_read_tdat(file, file_length, &tkey, &format, ordering, custom_table, name_list)?},
                     format: format,
                     aux_tables: IndexMap::new(),
                 });
@@ -1000,37 +1743,66 @@ impl GXTFile {
                         let version_number = u16::from_le_bytes(raw_version_number);
                         let character_size = u16::from_le_bytes(raw_character_size);
                     
-                        if version_number != 4 {return Err(GXTError::ParsingError(format!("The GXT file has version {}, must have version 4",version_number) ));}
+                        if version_number != 4 {return Err(GXTError::UnsupportedVersion(version_number));}
                         match character_size {
                             8 => (),
                             16 => (),
-                            _ => {return Err(GXTError::ParsingError(format!("The GXT file has character size {}, must have 8 or 16",character_size) ));}
+                            _ => {return Err(GXTError::UnsupportedCharacterSize(character_size));}
                         }
                     },
                     _ => {},
                 }
 
-                let tabl = gxt_read_tabl(file)?;
+                let tabl_offset = file.stream_position()?;
+                let tabl = gxt_read_tabl(file, file_length)?;
+
+                if !tabl.entries.first().is_some_and(|e| e.is_main) {
+                    return Err(GXTError::MissingMainTable);
+                }
 
-                if !tabl.entries[0].is_main {
-                    return Err(GXTError::ParsingError("GXT File error: The first table must be MAIN".to_string()));
+                // the games binary-search the tables after MAIN by name
+                if strict && !tabl.entries[1..].is_sorted_by(|a,b| a.name <= b.name) {
+                    return Err(GXTError::UnsortedEntries { section: "TABL", offset: tabl_offset, table: None });
                 }
 
-                let _tkeys: Result<Vec<GXTInternalTKEY>,_> = 
-                    tabl.entries.iter().map(|k| gxt_read_tkey(
+                // several tables may point at the same TKEY, so the limits apply to the whole
+                // file rather than to each table
+                let mut tkeys: Vec<GXTInternalTKEY> = vec!();
+                let mut string_count: usize = 0;
+                for k in &tabl.entries {
+                    let tkey = gxt_read_tkey(
                         file,
+                        file_length,
                         &format,
                         match k.is_main { true => None, false => Some(k.name), },
                         Some(k.offset),
-                        ordering
-                        )).collect();
-                let tkeys = _tkeys?;
+                        ordering,
+                        strict
+                        )?;
+                    string_count += tkey.entries.len();
+                    if string_count > MAX_ENTRIES {
+                        return Err(GXTError::TooLarge { what: "strings", limit: MAX_ENTRIES, table: Some(table_name_of(&tkey.name)) });
+                    }
+                    tkeys.push(tkey);
+                }
+                let mut total_length: usize = 0;
+                let mut check_length = |table: &IndexMap<String,String>, name: &Option<[u8;8]>| {
+                    total_length += table.values().map(|v| v.chars().count()).sum::<usize>();
+                    match total_length > MAX_TOTAL_LENGTH {
+                        true => Err(GXTError::TooLarge { what: "characters of text", limit: MAX_TOTAL_LENGTH, table: Some(table_name_of(name)) }),
+                        false => Ok(()),
+                    }
+                };
+                let (main_tkey, aux_tkeys) = match tkeys.split_first() {
+                    Some(t) => t,
+                    None => { return Err(GXTError::MissingMainTable); },
+                };
 
-                let mut _key_ordering: Vec<String> = tkeys[1..].iter().map(|k| match k.name {
+                let mut _key_ordering: Vec<String> = aux_tkeys.iter().map(|k| match k.name {
                     None => "".to_string(),
                     Some(n) => string_from_name(&GXTStringName::Text(n), name_list)
                 }).collect();
-                let mut _offset_ordering: Vec<(String,u32)> = tkeys[1..].iter().map(|k| (match k.name {
+                let mut _offset_ordering: Vec<(String,u32)> = aux_tkeys.iter().map(|k| (match k.name {
                     None => "".to_string(),
                     Some(n) => string_from_name(&GXTStringName::Text(n), name_list)
                 }, k.offset)).collect();
@@ -1038,21 +1810,15 @@ impl GXTFile {
                 _offset_ordering.sort_by(|a,b| (a.1).cmp(&b.1));
 
                 let mut aux_tables: IndexMap<String, IndexMap<String,String>> = IndexMap::new();
-                for e in &tkeys[1..] {
+                for e in aux_tkeys {
                     let name_string = match e.name {
-                        None => { return Err(GXTError::ParsingError("An auxiliary table must have a name!".to_string())); },
+                        None => { return Err(GXTError::InternalError("An auxiliary table must have a name".to_string())); },
                         Some(n) => string_from_name(&GXTStringName::Text(n), name_list)
                         };
 
-                    let new_table = gxt_read_tdat(file, &e, Some(e.offset), &format, ordering, custom_table, name_list);
-                    match new_table {
-                        Ok(t) => {
-                            aux_tables.insert(name_string.clone(), t);
-                        },
-                        Err(x) => {
-                            return Err(GXTError::ParsingError(format!("Error while parsing table ({}): {}",&name_string, x)));
-                        },
-                    };
+                    let new_table = gxt_read_tdat(file, file_length, e, &format, ordering, custom_table, name_list)?;
+                    check_length(&new_table, &e.name)?;
+                    aux_tables.insert(name_string, new_table);
                 }
 
                 //match ordering {
@@ -1064,8 +1830,10 @@ impl GXTFile {
                 //}
                 
                 //eprintln!("Reading main table...");
+                let main_table = gxt_read_tdat(file, file_length, main_tkey, &format, ordering, custom_table, name_list)?;
+                check_length(&main_table, &main_tkey.name)?;
                 return Ok(GXTFile {
-                    main_table: gxt_read_tdat(file, &tkeys[0], Some(tkeys[0].offset), &format, ordering, custom_table, name_list)?,
+                    main_table,
                     format: format,
                     aux_tables,
                 });
@@ -1241,4 +2009,350 @@ mod tests {
         
         assert!( compiled_data == comparison_data );
     }
+
+    #[test]
+    fn fallback_policy_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("QUOTE".to_string(),"“Łódź” — ok".to_string()),("PLAIN".to_string(),"Plain".to_string()),("QUOTE2".to_string(),"“Łódź” — ok".to_string())]),
+            IndexMap::from([("AUX1".to_string(),
+                    IndexMap::from([("CITY".to_string(),"Gdańsk".to_string())]))]),
+            );
+
+        let mut compiled_data: Vec<u8> = vec!();
+        let Err(_x) = x.write_to_gxt(&mut compiled_data,&None) else {
+            panic!("There should be an error, as the VC character table has no curly quotes");
+        };
+
+        let options = WriteOptions {
+            fallback: Some(GXTFallbackPolicy {
+                transliteration: HashMap::from([('“','"'.to_string()),('”','"'.to_string()),('—',"-".to_string()),('Ł',"L".to_string())]),
+                strip_diacritics: true,
+            }),
+            ..Default::default()
+        };
+
+        let mut compiled_data: Vec<u8> = vec!();
+        let report = x.write_to_gxt_with_options(&mut compiled_data,&options).expect("Unable to compile GXT file");
+
+        // ó is in the VC table, so it must not be replaced
+        let quote = &report.substitutions[MAIN_TABLE_NAME]["QUOTE"];
+        assert!( quote.len() == 5 );
+        assert!( quote[1].original == 'Ł' && quote[1].position == 1 );
+        assert!( quote[2].original == 'ź' && quote[2].replacement == "z" );
+        assert!( !report.substitutions[MAIN_TABLE_NAME].contains_key("PLAIN") );
+        assert!( report.substitutions["AUX1"]["CITY"][0].replacement == "n" );
+
+        // the second string reuses the first one's data, but its substitutions are still listed
+        assert!( report.substitutions[MAIN_TABLE_NAME]["QUOTE2"].len() == 5 );
+
+        let y = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&compiled_data),&None,&None,&None).expect("Unable to decompile GXT file");
+        assert!( y.main_table["QUOTE"] == "\"Lódz\" - ok" && y.main_table["QUOTE2"] == "\"Lódz\" - ok" );
+    }
+
+    #[test]
+    fn normalization_test() {
+
+        // "café", with the accent as a separate combining character
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("CAFE".to_string(),"cafe\u{301}".to_string())]),
+            IndexMap::new(),
+            );
+
+        let mut compiled_data: Vec<u8> = vec!();
+        let Err(_x) = x.write_to_gxt(&mut compiled_data,&None) else {
+            panic!("There should be an error, as the VC character table has no combining accents");
+        };
+
+        let options = WriteOptions {
+            normalization: Some(NormalizationForm::NFC),
+            ..Default::default()
+        };
+        let mut compiled_data: Vec<u8> = vec!();
+        x.write_to_gxt_with_options(&mut compiled_data,&options).expect("Unable to compile GXT file");
+        let y = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&compiled_data),&None,&None,&None).expect("Unable to decompile GXT file");
+        assert!( y.main_table["CAFE"] == "café" );
+
+        // a table that only knows the combining caron can encode "ě" in the "auto" mode only
+        let custom_table = Some(GXTCharacterTable {
+            decode_table: HashMap::from([(200,'\u{30C}')]),
+            encode_table: HashMap::from([('\u{30C}',200)]),
+        });
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("CARON".to_string(),"ě".to_string())]),
+            IndexMap::new(),
+            );
+
+        let mut options = WriteOptions {
+            custom_table: &custom_table,
+            normalization: Some(NormalizationForm::NFC),
+            ..Default::default()
+        };
+        let mut compiled_data: Vec<u8> = vec!();
+        let Err(_x) = x.write_to_gxt_with_options(&mut compiled_data,&options) else {
+            panic!("There should be an error, as NFC does not decompose characters");
+        };
+
+        options.normalization = Some(NormalizationForm::Auto);
+        let mut compiled_data: Vec<u8> = vec!();
+        x.write_to_gxt_with_options(&mut compiled_data,&options).expect("Unable to compile GXT file");
+        let y = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&compiled_data),&None,&custom_table,&None).expect("Unable to decompile GXT file");
+        assert!( y.main_table["CARON"] == "e\u{30C}" );
+    }
+
+    #[test]
+    fn tags_bypass_custom_tables_test() {
+
+        // the Russian VC table maps the Latin "k" to the Cyrillic "к", which would turn a "~k~"
+        // tag into "~к~" if tags were decoded through it
+        let _f = File::open("character_tables/vc_ru_tycoon.toml").expect("Unable to open character table");
+        let mut file = BufReader::new(_f);
+        let custom_table = Some(read_custom_table(&mut file).expect("Unable to read character table"));
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("HELP".to_string(),"нажми ~k~, ~h~как~w~ тут".to_string()),("TILDE".to_string(),"как ~ так".to_string())]),
+            IndexMap::new(),
+            );
+
+        let mut compiled_data: Vec<u8> = vec!();
+        x.write_to_gxt(&mut compiled_data,&custom_table).expect("Unable to compile GXT file");
+
+        // the tag's "k" is encoded using the same code as the Cyrillic "к"
+        let y = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&compiled_data),&None,&None,&None).expect("Unable to decompile GXT file");
+        assert!( y.main_table["HELP"].contains("~k~") );
+
+        let y = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&compiled_data),&None,&custom_table,&None).expect("Unable to decompile GXT file");
+        assert!( y.main_table == x.main_table );
+
+        // the Russian SA table does the same with the uppercase "K", used for the left trigger
+        let _f = File::open("character_tables/sa_ru_tycoon.toml").expect("Unable to open character table");
+        let mut file = BufReader::new(_f);
+        let custom_table = Some(read_custom_table(&mut file).expect("Unable to read character table"));
+
+        let x = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([("HELP".to_string(),"НАЖМИ ~k~~PED_FIREWEAPON~ И ~K~, ~r~КОТ~s~".to_string())]),
+            IndexMap::new(),
+            );
+
+        let mut compiled_data: Vec<u8> = vec!();
+        x.write_to_gxt(&mut compiled_data,&custom_table).expect("Unable to compile GXT file");
+
+        let y = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&compiled_data),&None,&custom_table,&None).expect("Unable to decompile GXT file");
+        assert!( y.main_table[0] == x.main_table[0] );
+    }
+
+    #[test]
+    fn error_locations_test() {
+
+        // compilation errors point at the table and string
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("HELLO".to_string(),"Hello world!".to_string())]),
+            IndexMap::from([("AUX1".to_string(),
+                    IndexMap::from([("POLISH".to_string(),"Żółw".to_string())]))]),
+            );
+        let Err(e) = x.write_to_gxt(&mut vec!(),&None) else { panic!("Ż can't be encoded in a VC file"); };
+        assert!( matches!(&e, GXTError::UnencodableCharacter { character: 'Ż', table: Some(t), key: Some(k) } if t == "AUX1" && k == "POLISH") );
+        assert!( e.to_string() == "GXT file compilation error in [AUX1] POLISH: character 'Ż' (U+017B) cannot be encoded" );
+
+        // a truncated file is told apart from a bad header
+        let mut data: Vec<u8> = vec!();
+        File::open("test_files/gtavc.gxt").unwrap().read_to_end(&mut data).unwrap();
+
+        let truncated = &data[..data.len() - 10];
+        let Err(e) = GXTFile::read_from_gxt(&mut std::io::Cursor::new(truncated),&None,&None,&None) else { panic!("The file is truncated"); };
+        assert!( matches!(e, GXTError::UnexpectedEnd { section: "TDAT", key: Some(_), .. }) );
+
+        let mut broken = data.clone();
+        broken[0x20..0x24].copy_from_slice(b"XXXX"); // the main table's TKEY header
+        let Err(e) = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&broken),&None,&None,&None) else { panic!("The header is broken"); };
+        assert!( matches!(e, GXTError::InvalidHeader { section: "TKEY", .. }) );
+
+        // TOML errors keep their location
+        let text = "format = \"Vice\"\n[main_table]\nHELLO = Hello\n";
+        let Err(e) = GXTFile::read_from_text(&mut std::io::Cursor::new(text)) else { panic!("The text file is invalid"); };
+        assert!( matches!(e, GXTError::TOMLDeError { line: Some(3), column: Some(9), .. }) );
+        assert!( e.to_string().starts_with("TOML deserialization error at line 3, column 9: ") );
+    }
+
+    #[test]
+    fn duplicate_hashes_test() {
+
+        // a name and its own hash end up as the same TKEY entry
+        let hash = format!("#{:08X}", string_to_name_crc32("FOO").unwrap());
+        let x = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([("FOO".to_string(),"Foo".to_string()),(hash.clone(),"Also foo".to_string())]),
+            IndexMap::new(),
+            );
+        let Err(e) = x.write_to_gxt(&mut vec!(),&None) else { panic!("FOO and its hash can't both be written"); };
+        assert!( matches!(e, GXTError::DuplicateName { key, other, table: Some(t) } if key == hash && other == "FOO" && t == "MAIN") );
+
+        // so do two different names with the same CRC32
+        let x = GXTFile::new(
+            GXTFileFormat::San16,
+            IndexMap::new(),
+            IndexMap::from([("AUX1".to_string(),
+                    IndexMap::from([("plumless".to_string(),"A".to_string()),("buckeroo".to_string(),"B".to_string())]))]),
+            );
+        assert!( string_to_name_crc32("plumless").unwrap() == string_to_name_crc32("buckeroo").unwrap() );
+        let Err(e) = x.write_to_gxt(&mut vec!(),&None) else { panic!("Colliding names can't both be written"); };
+        assert!( e.to_string() == "GXT file compilation error in [AUX1]: string name buckeroo is the same as or has the same hash as plumless" );
+    }
+
+    #[test]
+    fn table_ordering_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("HELLO".to_string(),"Hello".to_string())]),
+            IndexMap::from([
+                ("ZZZ".to_string(), IndexMap::from([("Z".to_string(),"Z".to_string())])),
+                ("AAA".to_string(), IndexMap::from([("A".to_string(),"A".to_string())])),
+            ]),
+            );
+
+        let mut compiled_data: Vec<u8> = vec!();
+        x.write_to_gxt(&mut compiled_data,&None).unwrap();
+
+        // MAIN stays first, the other tables get sorted
+        assert!( compiled_data[8..12] == *b"MAIN" && compiled_data[20..23] == *b"AAA" && compiled_data[32..35] == *b"ZZZ" );
+        let y = GXTFile::read_from_gxt_strict(&mut std::io::Cursor::new(&compiled_data),&None,&None,&None).unwrap();
+        assert!( y.aux_tables["ZZZ"]["Z"] == "Z" && y.aux_tables["AAA"]["A"] == "A" );
+
+        // swap the two TABL entries back, the file is still readable, but not in strict mode
+        let mut unsorted = compiled_data.clone();
+        unsorted[20..32].copy_from_slice(&compiled_data[32..44]);
+        unsorted[32..44].copy_from_slice(&compiled_data[20..32]);
+        assert!( GXTFile::read_from_gxt(&mut std::io::Cursor::new(&unsorted),&None,&None,&None).is_ok() );
+        let Err(e) = GXTFile::read_from_gxt_strict(&mut std::io::Cursor::new(&unsorted),&None,&None,&None) else { panic!("TABL is not sorted"); };
+        assert!( matches!(e, GXTError::UnsortedEntries { section: "TABL", table: None, .. }) );
+    }
+
+    #[test]
+    fn string_ordering_test() {
+
+        let mut data: Vec<u8> = vec!();
+        File::open("test_files/gta3.gxt").unwrap().read_to_end(&mut data).unwrap();
+        assert!( GXTFile::read_from_gxt_strict(&mut std::io::Cursor::new(&data),&None,&None,&None).is_ok() );
+
+        // swap the first two TKEY entries
+        let original = data.clone();
+        data[8..20].copy_from_slice(&original[20..32]);
+        data[20..32].copy_from_slice(&original[8..20]);
+        assert!( GXTFile::read_from_gxt(&mut std::io::Cursor::new(&data),&None,&None,&None).is_ok() );
+        let Err(e) = GXTFile::read_from_gxt_strict(&mut std::io::Cursor::new(&data),&None,&None,&None) else { panic!("TKEY is not sorted"); };
+        assert!( e.to_string() == "GXT file parsing error in [MAIN]: TKEY entries at offset 0x0 are not sorted" );
+    }
+
+    #[test]
+    fn corrupt_input_test() {
+
+        let read = |data: &[u8]| GXTFile::read_from_gxt(&mut std::io::Cursor::new(data),&None,&None,&None);
+
+        // a TABL without any entries
+        assert!( matches!(read(b"TABL\0\0\0\0"), Err(GXTError::MissingMainTable)) );
+
+        let mut original: Vec<u8> = vec!();
+        File::open("test_files/gta3.gxt").unwrap().read_to_end(&mut original).unwrap();
+
+        // a TKEY size larger than the file
+        let mut data = original.clone();
+        data[4..8].copy_from_slice(&0xFFFFFFF0u32.to_le_bytes());
+        assert!( matches!(read(&data), Err(GXTError::UnexpectedEnd { section: "TKEY", .. })) );
+
+        // a string offset outside of TDAT
+        let mut data = original.clone();
+        data[8..12].copy_from_slice(&0x7FFFFFFFu32.to_le_bytes());
+        let Err(e) = read(&data) else { panic!("the offset is out of bounds"); };
+        assert!( matches!(e, GXTError::OutOfBounds { section: "TDAT", key: Some(ref k), .. } if k == "FEM_MM") );
+
+        // a string longer than any game would read can't be written
+        let x = GXTFile::new(GXTFileFormat::San8, IndexMap::from([("LONG".to_string(),"a".repeat(MAX_STRING_LENGTH+1))]), IndexMap::new());
+        let Err(e) = x.write_to_gxt(&mut vec!(),&None) else { panic!("the string is too long"); };
+        assert!( e.to_string() == format!("GXT file compilation error in [MAIN] LONG: more than {MAX_STRING_LENGTH} characters in a string, which can't be read back") );
+
+        // ...and isn't read either, once its terminator is overwritten
+        let x = GXTFile::new(GXTFileFormat::San8, IndexMap::from([
+            ("LONG".to_string(),"a".repeat(MAX_STRING_LENGTH)),
+            ("NEXT".to_string(),"b".to_string()),
+            ]), IndexMap::new());
+        let mut data: Vec<u8> = vec!();
+        x.write_to_gxt(&mut data,&None).unwrap();
+        assert!( read(&data).is_ok() );
+        let tdat = data.windows(4).position(|w| w == b"TDAT").unwrap() + 8;
+        data[tdat + MAX_STRING_LENGTH] = b'a';
+        assert!( matches!(read(&data), Err(GXTError::StringTooLong { offset, .. }) if offset == tdat as u64) );
+
+        // many entries pointing at the same long string
+        let mut strings = IndexMap::from([("K0".to_string(),"a".repeat(60000))]);
+        strings.extend((1..300).map(|i| (format!("K{i}"), "b".to_string())));
+        let mut data: Vec<u8> = vec!();
+        GXTFile::new(GXTFileFormat::San8, strings, IndexMap::new()).write_to_gxt(&mut data,&None).unwrap();
+        assert!( read(&data).is_ok() );
+        for entry in data[32..32 + 8*300].chunks_exact_mut(8) {
+            entry[0..4].copy_from_slice(&0u32.to_le_bytes());
+        }
+        let Err(e) = read(&data) else { panic!("the text is too long"); };
+        assert!( e.to_string() == format!("GXT file parsing error in [MAIN]: the file has more than {MAX_TOTAL_LENGTH} characters of text") );
+
+        // a TABL listing more tables than any file has
+        let mut data = b"TABL".to_vec();
+        data.extend_from_slice(&(12 * (MAX_TABLES as u32 + 1)).to_le_bytes());
+        data.resize(8 + 12 * (MAX_TABLES + 1), 0);
+        assert!( matches!(read(&data), Err(GXTError::TooLarge { what: "tables", .. })) );
+
+        // no truncation or single-byte corruption of the test files may cause a panic
+        for f in ["test_files/gta3.gxt", "test_files/gtavc.gxt", "test_files/gtasa.gxt"] {
+            let mut original: Vec<u8> = vec!();
+            File::open(f).unwrap().read_to_end(&mut original).unwrap();
+
+            for length in 0..original.len() {
+                assert!( read(&original[..length]).is_err() );
+            }
+            for index in 0..original.len() {
+                for value in [0x00, 0x7F, 0x80, 0xFF] {
+                    let mut data = original.clone();
+                    data[index] = value;
+                    let _ = read(&data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn oversize_file_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("HELLO".to_string(),"Hello".to_string())]),
+            IndexMap::from([("AUX".to_string(), IndexMap::from([("A".to_string(),"A".to_string())]))]),
+            );
+        let mut report = GXTWriteReport::default();
+        let main_data = x.create_tkey(&x.main_table, None, &Default::default(), &mut report).unwrap();
+        let aux_data = x.create_tkey(&x.aux_tables["AUX"], Some("AUX"), &Default::default(), &mut report).unwrap();
+
+        // a main table just small enough for its own offsets, but pushing the next table past 4 GiB
+        let mut large_main = main_data.clone();
+        large_main.0.size = 0xFFFF_FFF0;
+        let mut compiled_data: Vec<u8> = vec!();
+        let Err(e) = x.write_compiled_to_gxt(&mut compiled_data, &large_main, std::slice::from_ref(&aux_data)) else { panic!("the file is too large"); };
+        assert!( matches!(e, GXTError::OffsetTooLarge { offset: 0x1_0000_0000.., table: Some(ref t) } if t == "AUX") );
+        assert!( compiled_data.is_empty() );
+
+        // the same tables are fine when they are small
+        x.write_compiled_to_gxt(&mut compiled_data, &main_data, &[aux_data]).unwrap();
+        let y = GXTFile::read_from_gxt(&mut std::io::Cursor::new(&compiled_data),&None,&None,&None).unwrap();
+        assert!( y.main_table["HELLO"] == "Hello" && y.aux_tables["AUX"]["A"] == "A" );
+
+        // sizes that don't fit into 32 bits
+        let Err(e) = section_size(0x1_0000_0000, "TDAT", "MAIN") else { panic!("the section is too large"); };
+        assert!( e.to_string() == "GXT file compilation error in [MAIN]: TDAT section would be 4294967296 bytes long, more than a GXT file can hold" );
+        assert!( section_size(0xFFFF_FFFF, "TDAT", "MAIN").is_ok() );
+    }
 }
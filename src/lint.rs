@@ -0,0 +1,175 @@
+//! This module is used to find mistakes in the tilde-based tags of a GXT file's strings.
+//!
+//! A single missing tilde in a translated string makes the game treat the rest of the string as a
+//! tag, so it silently disappears. [GXTFile::lint] goes through every string in a file and
+//! reports problems like that as a list of [GXTLintDiagnostic]s, each pointing at the table, the
+//! string and the position inside the string where the problem was found.
+//!
+use std::fmt;
+use crate::tags::{GXTColor, GXTToken, is_known_action, tokenize};
+use crate::{GXTFile, GXTFileFormat};
+
+/// Describes a single problem found by the linter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GXTLintKind {
+    /// The string has an odd number of tildes, so the last tag is never closed.
+    UnclosedTag,
+    /// The tag is not known for the file's format.
+    UnknownTag(String),
+    /// The tag is empty (`~~`).
+    EmptyTag,
+    /// The string switches to a different color and never switches back to the default one.
+    /// Strings that start with a color tag are not reported, as coloring the whole string is
+    /// usually intended.
+    ColorNotReset(String),
+    /// A `~k~` tag isn't followed by an action name tag.
+    MissingAction,
+    /// A `~k~` tag is followed by a name that isn't one of the known actions.
+    UnknownAction(String),
+}
+
+/// Describes a problem found by the linter, along with its location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTLintDiagnostic {
+    /// The name of the table (the main table is called [crate::MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The name of the string.
+    pub key: String,
+    /// The position in the string where the problem starts, counted in characters, not bytes.
+    pub position: usize,
+    /// The problem itself.
+    pub kind: GXTLintKind,
+}
+
+impl fmt::Display for GXTLintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GXTLintKind::UnclosedTag => write!(f, "odd number of tildes, the last tag is never closed"),
+            GXTLintKind::UnknownTag(t) => write!(f, "unknown tag ~{t}~"),
+            GXTLintKind::EmptyTag => write!(f, "empty tag ~~"),
+            GXTLintKind::ColorNotReset(t) => write!(f, "color tag ~{t}~ is never reset"),
+            GXTLintKind::MissingAction => write!(f, "~k~ tag without an action name"),
+            GXTLintKind::UnknownAction(a) => write!(f, "~k~ tag with an unknown action name ~{a}~"),
+        }
+    }
+}
+
+impl fmt::Display for GXTLintDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}, position {}: {}", self.table, self.key, self.position, self.kind)
+    }
+}
+
+// whether a color tag brings the text back to its usual color
+fn is_reset_color(color: &GXTColor, format: &GXTFileFormat) -> bool {
+    match format {
+        GXTFileFormat::Three | GXTFileFormat::Vice => *color == GXTColor::White,
+        GXTFileFormat::San8 | GXTFileFormat::San16 => matches!(color, GXTColor::Default | GXTColor::White),
+    }
+}
+
+/// Checks a single string for problems with its tags. Returns a list of problems along with their
+/// positions in the string (counted in characters).
+pub fn lint_string(string: &str, format: &GXTFileFormat) -> Vec<(usize, GXTLintKind)> {
+
+    let mut res: Vec<(usize, GXTLintKind)> = vec!();
+
+    // position and contents of the last color tag that didn't reset the color
+    let mut unreset_color: Option<(usize, String)> = None;
+    let mut position: usize = 0;
+
+    for t in tokenize(string, format) {
+        match &t {
+            GXTToken::UnclosedTag(_) => { res.push((position, GXTLintKind::UnclosedTag)); },
+            GXTToken::Unknown(tag) if tag.is_empty() => { res.push((position, GXTLintKind::EmptyTag)); },
+            GXTToken::Unknown(tag) => { res.push((position, GXTLintKind::UnknownTag(tag.clone()))); },
+            GXTToken::KeyBinding(None) => { res.push((position, GXTLintKind::MissingAction)); },
+            GXTToken::KeyBinding(Some(action)) if !is_known_action(action) => {
+                res.push((position, GXTLintKind::UnknownAction(action.clone())));
+            },
+            GXTToken::Color { tag, color } => {
+                if is_reset_color(color, format) {
+                    unreset_color = None;
+                } else if position > 0 {
+                    unreset_color = Some((position, tag.clone()));
+                }
+            },
+            _ => {},
+        }
+        position += t.to_string().chars().count();
+    }
+
+    if let Some((p, tag)) = unreset_color {
+        res.push((p, GXTLintKind::ColorNotReset(tag)));
+    }
+
+    res.sort_by_key(|(p, _)| *p);
+    res
+}
+
+impl GXTFile {
+    /// Checks every string in the file for problems with its tags, according to the file's
+    /// format. See [GXTLintKind] for the list of problems that are reported.
+    pub fn lint(&self) -> Vec<GXTLintDiagnostic> {
+
+        let mut res: Vec<GXTLintDiagnostic> = vec!();
+
+        for (table, strings) in self.tables() {
+            for (key, value) in strings {
+                for (position, kind) in lint_string(value, &self.format) {
+                    res.push(GXTLintDiagnostic {
+                        table: table.to_string(),
+                        key: key.clone(),
+                        position,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+    use super::*;
+
+    #[test]
+    fn lint_string_test() {
+
+        assert!( lint_string("~s~Press ~k~~PED_FIREWEAPON~ to ~r~shoot~s~.", &GXTFileFormat::San8).is_empty() );
+        assert!( lint_string("~r~WASTED", &GXTFileFormat::Vice).is_empty() );
+
+        assert!( lint_string("Press ~k~ to ~r~shoot", &GXTFileFormat::San8) == vec!(
+            (6, GXTLintKind::MissingAction),
+            (13, GXTLintKind::ColorNotReset("r".to_string())),
+        ));
+        assert!( lint_string("~k~~PED_FIREWEAPN~ ~~ ~q~", &GXTFileFormat::Vice) == vec!(
+            (0, GXTLintKind::UnknownAction("PED_FIREWEAPN".to_string())),
+            (19, GXTLintKind::EmptyTag),
+            (22, GXTLintKind::UnknownTag("q".to_string())),
+        ));
+        assert!( lint_string("Go ~r~here~w and stay", &GXTFileFormat::Three) == vec!(
+            (3, GXTLintKind::ColorNotReset("r".to_string())),
+            (10, GXTLintKind::UnclosedTag),
+        ));
+    }
+
+    #[test]
+    fn lint_file_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("OK".to_string(),"~h~Fine~w~".to_string())]),
+            IndexMap::from([("AUX1".to_string(),
+                    IndexMap::from([("BROKEN".to_string(),"Oops ~r".to_string())]))]),
+            );
+
+        let diagnostics = x.lint();
+        assert!( diagnostics.len() == 1 );
+        assert!( diagnostics[0].table == "AUX1" && diagnostics[0].key == "BROKEN" && diagnostics[0].position == 5 );
+        assert!( diagnostics[0].to_string() == "[AUX1] BROKEN, position 5: odd number of tildes, the last tag is never closed" );
+    }
+}
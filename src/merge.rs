@@ -0,0 +1,358 @@
+//! This module is used to combine several GXT files into one, such as applying text mods on top of
+//! a stock file.
+//!
+//! [GXTFile::merge] copies the strings and tables of another file into this one. Strings and
+//! tables that only exist in the other file are simply added, while those that exist in both files
+//! with different contents are *conflicts*, resolved according to a [MergePolicy]. The policy
+//! decides whether conflicts are resolved for each string separately, or for whole tables at once,
+//! and which side wins: this file, the other one, a callback deciding each conflict, or none (in
+//! which case the merge fails). Every change made is listed in the returned [GXTMergeReport].
+//!
+//! Strings are copied as they are, so both files must be of the same format. A file for a
+//! different game can be converted first using [GXTFile::convert_to]. In GTA SA files, strings are
+//! matched by the CRC32 hash of their names, so a mod that uses names overrides the strings of a
+//! decompiled file that only knows their hashes.
+//!
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fmt;
+use crate::{GXTError, GXTFile, GXTFileFormat, MAIN_TABLE_NAME};
+use crate::diff::{GXTKeyId, key_id};
+
+/// Decides which version of a conflicting string or table is kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GXTMergeResolution {
+    /// Keep the version in this file.
+    Ours,
+    /// Take the version from the other file.
+    Theirs,
+    /// Stop merging and return a [GXTError::MergeConflict].
+    Fail,
+}
+
+/// A string or table that exists in both files being merged, with different contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GXTMergeConflict<'a> {
+    /// A string with the same name but a different value.
+    Key {
+        /// The name of the table.
+        table: &'a str,
+        /// The name of the string.
+        key: &'a str,
+        /// The value in this file.
+        ours: &'a str,
+        /// The value in the other file.
+        theirs: &'a str,
+    },
+    /// A table with the same name but different contents.
+    Table {
+        /// The name of the table.
+        table: &'a str,
+        /// The table in this file.
+        ours: &'a IndexMap<String,String>,
+        /// The table in the other file.
+        theirs: &'a IndexMap<String,String>,
+    },
+}
+
+/// How conflicts are resolved.
+pub enum MergeStrategy<'a> {
+    /// Always keep the version in this file.
+    Ours,
+    /// Always take the version from the other file.
+    Theirs,
+    /// Fail on the first conflict.
+    Fail,
+    /// Call a function for every conflict to decide which version to keep.
+    Callback(Box<dyn FnMut(&GXTMergeConflict) -> GXTMergeResolution + 'a>),
+}
+
+/// Whether conflicts are resolved for each string, or for whole tables.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeGranularity {
+    /// Strings from both versions of a table are combined, and only strings with the same name
+    /// but different values conflict.
+    #[default]
+    Key,
+    /// A table that differs between the files conflicts as a whole, and the version kept replaces
+    /// the other one entirely.
+    Table,
+}
+
+/// Describes how [GXTFile::merge] resolves conflicts.
+pub struct MergePolicy<'a> {
+    /// Which version of a conflicting string or table is kept.
+    pub strategy: MergeStrategy<'a>,
+    /// Whether conflicts are resolved for each string, or for whole tables.
+    pub granularity: MergeGranularity,
+}
+
+impl<'a> MergePolicy<'a> {
+    /// A policy resolving conflicts for each string using the specified strategy.
+    pub fn new(strategy: MergeStrategy<'a>) -> MergePolicy<'a> {
+        MergePolicy { strategy, granularity: MergeGranularity::Key }
+    }
+
+    // decides a single conflict
+    fn resolve(&mut self, conflict: &GXTMergeConflict) -> GXTMergeResolution {
+        match &mut self.strategy {
+            MergeStrategy::Ours => GXTMergeResolution::Ours,
+            MergeStrategy::Theirs => GXTMergeResolution::Theirs,
+            MergeStrategy::Fail => GXTMergeResolution::Fail,
+            MergeStrategy::Callback(f) => f(conflict),
+        }
+    }
+}
+
+/// Describes what happened to a string or table during a merge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GXTMergeChangeKind {
+    /// It only existed in the other file, and was added.
+    Added,
+    /// It conflicted, and the version from the other file replaced this one.
+    Replaced,
+    /// It conflicted, and the version in this file was kept.
+    Kept,
+}
+
+/// A single change made (or, for conflicts resolved in this file's favour, not made) by a merge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTMergeChange {
+    /// The name of the table (the main table is called [MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The name of the string, or None if the change concerns the whole table.
+    pub key: Option<String>,
+    /// What happened to it.
+    pub kind: GXTMergeChangeKind,
+}
+
+impl fmt::Display for GXTMergeChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(k) => write!(f, "[{}] {}: ", self.table, k)?,
+            None => write!(f, "[{}]: ", self.table)?,
+        }
+        match (&self.key, self.kind) {
+            (Some(_), GXTMergeChangeKind::Added) => write!(f, "string added"),
+            (None, GXTMergeChangeKind::Added) => write!(f, "table added"),
+            (_, GXTMergeChangeKind::Replaced) => write!(f, "conflict, replaced with the other version"),
+            (_, GXTMergeChangeKind::Kept) => write!(f, "conflict, kept the original version"),
+        }
+    }
+}
+
+/// Lists the changes made by [GXTFile::merge].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GXTMergeReport {
+    /// Every string or table added, replaced or kept in a conflict, in the order of the other file.
+    pub changes: Vec<GXTMergeChange>,
+}
+
+impl GXTMergeReport {
+    /// Returns the number of conflicts, however they were resolved.
+    pub fn conflict_count(&self) -> usize {
+        self.changes.iter().filter(|c| c.kind != GXTMergeChangeKind::Added).count()
+    }
+}
+
+// the strings of a table by their names (or hashes), so that tables can be compared regardless of
+// how their strings are named
+fn values_by_id(table: &IndexMap<String,String>, hashed: bool) -> HashMap<GXTKeyId, &String> {
+    table.iter().map(|(k,v)| (key_id(k, hashed), v)).collect()
+}
+
+impl GXTFile {
+    /// Copies the strings and tables of another file into this one, resolving conflicts according
+    /// to the [MergePolicy], and returns a report of the changes made.
+    ///
+    /// If a conflict is resolved with [GXTMergeResolution::Fail], a [GXTError::MergeConflict] is
+    /// returned and this file is left unchanged. Files of different formats can't be merged.
+    ///
+    /// In GTA SA files, a string's name and its `#XXXXXXXX` hash are the same string. When such a
+    /// string is replaced, the name used in this file is kept.
+    pub fn merge(&mut self, other: &GXTFile, mut policy: MergePolicy) -> Result<GXTMergeReport, GXTError> {
+
+        if self.format != other.format {
+            return Err(GXTError::MergeFormatMismatch { ours: self.format, theirs: other.format });
+        }
+        let hashed = matches!(self.format, GXTFileFormat::San8 | GXTFileFormat::San16);
+        let ids = |t: &IndexMap<String,String>| -> HashMap<GXTKeyId, String> {
+            t.keys().map(|k| (key_id(k, hashed), k.clone())).collect()
+        };
+
+        let mut merged = self.clone();
+        let mut report = GXTMergeReport::default();
+
+        for (table, theirs) in other.tables() {
+
+            let ours = if table == MAIN_TABLE_NAME {
+                &mut merged.main_table
+            } else if let Some(t) = merged.aux_tables.get_mut(table) {
+                t
+            } else {
+                merged.aux_tables.insert(table.to_string(), theirs.clone());
+                report.changes.push(GXTMergeChange { table: table.to_string(), key: None, kind: GXTMergeChangeKind::Added });
+                continue;
+            };
+
+            match policy.granularity {
+                MergeGranularity::Key => {
+                    let our_keys = ids(ours);
+                    for (key, their_value) in theirs {
+                        let our_value = our_keys.get(&key_id(key, hashed)).and_then(|k| ours.get_mut(k));
+                        let Some(our_value) = our_value else {
+                            ours.insert(key.clone(), their_value.clone());
+                            report.changes.push(GXTMergeChange { table: table.to_string(), key: Some(key.clone()), kind: GXTMergeChangeKind::Added });
+                            continue;
+                        };
+                        if our_value == their_value {
+                            continue;
+                        }
+
+                        let conflict = GXTMergeConflict::Key { table, key, ours: our_value, theirs: their_value };
+                        let kind = match policy.resolve(&conflict) {
+                            GXTMergeResolution::Ours => GXTMergeChangeKind::Kept,
+                            GXTMergeResolution::Theirs => {
+                                *our_value = their_value.clone();
+                                GXTMergeChangeKind::Replaced
+                            },
+                            GXTMergeResolution::Fail => {
+                                return Err(GXTError::MergeConflict { table: table.to_string(), key: Some(key.clone()) });
+                            },
+                        };
+                        report.changes.push(GXTMergeChange { table: table.to_string(), key: Some(key.clone()), kind });
+                    }
+                },
+                MergeGranularity::Table => {
+                    if values_by_id(ours, hashed) == values_by_id(theirs, hashed) {
+                        continue;
+                    }
+
+                    let conflict = GXTMergeConflict::Table { table, ours, theirs };
+                    let kind = match policy.resolve(&conflict) {
+                        GXTMergeResolution::Ours => GXTMergeChangeKind::Kept,
+                        GXTMergeResolution::Theirs => {
+                            *ours = theirs.clone();
+                            GXTMergeChangeKind::Replaced
+                        },
+                        GXTMergeResolution::Fail => {
+                            return Err(GXTError::MergeConflict { table: table.to_string(), key: None });
+                        },
+                    };
+                    report.changes.push(GXTMergeChange { table: table.to_string(), key: None, kind });
+                },
+            }
+        }
+
+        *self = merged;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GXTFileFormat;
+    use super::*;
+
+    fn base() -> GXTFile {
+        GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hello".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+            ]),
+            IndexMap::from([("AUX1".to_string(), IndexMap::from([("A".to_string(),"A".to_string())]))]),
+            )
+    }
+
+    fn modification() -> GXTFile {
+        GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hi".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+                ("NEW".to_string(),"New".to_string()),
+            ]),
+            IndexMap::from([
+                ("AUX1".to_string(), IndexMap::from([("B".to_string(),"B".to_string())])),
+                ("AUX2".to_string(), IndexMap::from([("C".to_string(),"C".to_string())])),
+            ]),
+            )
+    }
+
+    #[test]
+    fn merge_keys_test() {
+
+        let mut x = base();
+        let report = x.merge(&modification(), MergePolicy::new(MergeStrategy::Theirs)).unwrap();
+
+        assert!( x.main_table["HELLO"] == "Hi" && x.main_table["NEW"] == "New" );
+        assert!( x.aux_tables["AUX1"].len() == 2 && x.aux_tables["AUX2"]["C"] == "C" );
+        assert!( report.conflict_count() == 1 );
+        assert!( report.changes.iter().map(|c| c.to_string()).collect::<Vec<_>>() == vec!(
+            "[MAIN] HELLO: conflict, replaced with the other version",
+            "[MAIN] NEW: string added",
+            "[AUX1] B: string added",
+            "[AUX2]: table added",
+        ));
+
+        let mut x = base();
+        x.merge(&modification(), MergePolicy::new(MergeStrategy::Ours)).unwrap();
+        assert!( x.main_table["HELLO"] == "Hello" && x.main_table["NEW"] == "New" );
+
+        // a failed merge leaves the file unchanged
+        let mut x = base();
+        let Err(e) = x.merge(&modification(), MergePolicy::new(MergeStrategy::Fail)) else { panic!("HELLO conflicts"); };
+        assert!( matches!(e, GXTError::MergeConflict { ref table, key: Some(ref k) } if table == "MAIN" && k == "HELLO") );
+        assert!( x == base() );
+
+        let mut conflicts: Vec<String> = vec!();
+        let mut x = base();
+        x.merge(&modification(), MergePolicy::new(MergeStrategy::Callback(Box::new(|c| {
+            if let GXTMergeConflict::Key { key, ours, theirs, .. } = c {
+                conflicts.push(format!("{key}: {ours} / {theirs}"));
+            }
+            GXTMergeResolution::Ours
+        })))).unwrap();
+        assert!( conflicts == vec!("HELLO: Hello / Hi") );
+    }
+
+    #[test]
+    fn merge_tables_test() {
+
+        let mut x = base();
+        let report = x.merge(&modification(), MergePolicy { strategy: MergeStrategy::Theirs, granularity: MergeGranularity::Table }).unwrap();
+
+        // whole tables are replaced, so AUX1 loses its original string
+        assert!( x.main_table.len() == 3 && x.main_table["HELLO"] == "Hi" );
+        assert!( x.aux_tables["AUX1"].len() == 1 && x.aux_tables["AUX1"]["B"] == "B" );
+        assert!( report.changes == vec!(
+            GXTMergeChange { table: "MAIN".to_string(), key: None, kind: GXTMergeChangeKind::Replaced },
+            GXTMergeChange { table: "AUX1".to_string(), key: None, kind: GXTMergeChangeKind::Replaced },
+            GXTMergeChange { table: "AUX2".to_string(), key: None, kind: GXTMergeChangeKind::Added },
+        ));
+
+        // merging a file into itself changes nothing
+        let mut x = base();
+        assert!( x.merge(&base(), MergePolicy { strategy: MergeStrategy::Fail, granularity: MergeGranularity::Table }).unwrap().changes.is_empty() );
+    }
+
+    #[test]
+    fn merge_hashes_test() {
+
+        // a decompiled file without a name list only knows the hashes, which match the mod's names
+        let hash = format!("#{:08X}", crate::string_to_name_crc32("HELLO").unwrap());
+        let mut x = base();
+        x.main_table = IndexMap::from([(hash.clone(),"Hello".to_string()), ("BYE".to_string(),"Bye".to_string())]);
+
+        let report = x.merge(&modification(), MergePolicy::new(MergeStrategy::Theirs)).unwrap();
+        assert!( x.main_table.len() == 3 && x.main_table[&hash] == "Hi" && !x.main_table.contains_key("HELLO") );
+        assert!( report.conflict_count() == 1 );
+
+        // files of different formats can't be merged
+        let mut other = modification();
+        other.format = GXTFileFormat::Vice;
+        let mut x = base();
+        assert!( matches!(x.merge(&other, MergePolicy::new(MergeStrategy::Theirs)), Err(GXTError::MergeFormatMismatch { .. })) );
+    }
+}
@@ -0,0 +1,345 @@
+//! This module is used to describe a small set of changes to a GXT file, and apply them to
+//! another copy of it.
+//!
+//! A text mod that changes a few strings doesn't need to ship a whole GXT file (which would undo
+//! every other mod's changes, and contain the game's copyrighted text). Instead, it can ship a
+//! [GXTPatch], listing the strings to set or delete and the tables to add, rename or remove, and
+//! have it applied onto the user's own file with [GXTFile::apply_patch].
+//!
+//! Patches are stored as TOML files:
+//!
+//! ```toml
+//! add_tables = ["NEWMISS"]
+//! remove_tables = ["OLDMISS"]
+//!
+//! [[rename_tables]]
+//! from = "MISS1"
+//! to = "MISS2"
+//!
+//! [[set]]
+//! table = "MAIN"
+//! key = "FEM_MM"
+//! value = "Main Menu"
+//! original = "MAIN MENU"
+//!
+//! [[delete]]
+//! table = "MAIN"
+//! key = "FEM_OK"
+//! ```
+//!
+//! The `original` field is optional, and if present, the patch only applies if the file still
+//! has that text, so a patch made for a different version of the file isn't applied by accident.
+//! A patch can be made from the differences between two files using [GXTPatch::from_diff].
+//!
+use indexmap::IndexMap;
+use std::io::prelude::*;
+use crate::{GXTError, GXTFile, GXTFileFormat, MAIN_TABLE_NAME};
+use crate::diff::{GXTDiff, GXTTableStatus, key_id};
+use crate::parse_toml;
+
+/// Sets a string to a new value, adding it if it doesn't exist yet.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GXTPatchSet {
+    /// The name of the table (the main table is called [MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The name of the string.
+    pub key: String,
+    /// The new value.
+    pub value: String,
+    /// The value the string must have before the patch is applied. If None, the string may have
+    /// any value, or not exist at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+}
+
+/// Deletes an existing string.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GXTPatchDelete {
+    /// The name of the table (the main table is called [MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The name of the string.
+    pub key: String,
+    /// The value the string must have before it is deleted. If None, it may have any value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+}
+
+/// Renames an auxiliary table, keeping its strings.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GXTPatchRename {
+    /// The current name of the table. It must exist.
+    pub from: String,
+    /// The new name of the table. It must not exist yet.
+    pub to: String,
+}
+
+/// A set of changes to a GXT file. When applied, tables are added first, then renamed, then
+/// strings are set and deleted, then tables are removed.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GXTPatch {
+    /// Empty auxiliary tables to add. They must not exist yet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add_tables: Vec<String>,
+    /// Auxiliary tables to remove, along with all of their strings. They must exist.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_tables: Vec<String>,
+    /// Auxiliary tables to rename.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rename_tables: Vec<GXTPatchRename>,
+    /// Strings to set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub set: Vec<GXTPatchSet>,
+    /// Strings to delete. They must exist.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delete: Vec<GXTPatchDelete>,
+}
+
+impl GXTPatch {
+    /// Makes a patch that turns the old file of a [GXTDiff] into the new one. Every string it
+    /// changes or deletes has its original value recorded, so the patch only applies to files
+    /// that have the same text as the old file.
+    pub fn from_diff(diff: &GXTDiff) -> GXTPatch {
+
+        let mut res = GXTPatch::default();
+
+        for t in &diff.tables {
+            match &t.status {
+                GXTTableStatus::Added => { res.add_tables.push(t.table.clone()); },
+                GXTTableStatus::Removed => {
+                    // deleting the strings one by one would only make the patch longer
+                    res.remove_tables.push(t.table.clone());
+                    continue;
+                },
+                GXTTableStatus::Renamed { from } => {
+                    // the strings that didn't change are only in the old file, so they have to
+                    // move along with the table
+                    res.rename_tables.push(GXTPatchRename { from: from.clone(), to: t.table.clone() });
+                },
+                GXTTableStatus::Changed => {},
+            }
+
+            for (k,v) in &t.added {
+                res.set.push(GXTPatchSet { table: t.table.clone(), key: k.clone(), value: v.clone(), original: None });
+            }
+            for (k,v) in &t.changed {
+                res.set.push(GXTPatchSet {
+                    table: t.table.clone(),
+                    key: k.clone(),
+                    value: v.new.clone(),
+                    original: Some(v.old.clone()),
+                });
+            }
+            for (k,v) in &t.removed {
+                res.delete.push(GXTPatchDelete { table: t.table.clone(), key: k.clone(), original: Some(v.clone()) });
+            }
+        }
+
+        res
+    }
+
+    /// Returns true if the patch doesn't change anything.
+    pub fn is_empty(&self) -> bool {
+        self.add_tables.is_empty() && self.remove_tables.is_empty() && self.rename_tables.is_empty() && self.set.is_empty() && self.delete.is_empty()
+    }
+
+    /// Write the patch as a TOML file.
+    pub fn write_to_text(&self, file: &mut impl Write) -> Result<(),GXTError> {
+        let out_string = toml::to_string(self)?;
+        file.write_all(out_string.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Read a patch from a TOML file.
+pub fn read_patch(file: &mut (impl Read + std::io::Seek)) -> Result<GXTPatch,GXTError> {
+
+    let mut raw_data: String = Default::default();
+    file.read_to_string(&mut raw_data)?;
+
+    let patch: GXTPatch = parse_toml(&raw_data)?;
+    Ok(patch)
+}
+
+// finds the name a string is stored under in a table. in GTA SA files, the patch and the file
+// may refer to the same string by its name and by its hash
+fn find_key(table: &IndexMap<String,String>, key: &str, format: &GXTFileFormat) -> Option<String> {
+    if table.contains_key(key) {
+        return Some(key.to_string());
+    }
+    let hashed = matches!(format, GXTFileFormat::San8 | GXTFileFormat::San16);
+    let id = key_id(key, hashed);
+    table.keys().find(|k| hashed && key_id(k, hashed) == id).cloned()
+}
+
+impl GXTFile {
+
+    // returns a table by name, with the main table called MAIN_TABLE_NAME
+    fn table_mut(&mut self, table: &str) -> Option<&mut IndexMap<String,String>> {
+        match table == MAIN_TABLE_NAME {
+            true => Some(&mut self.main_table),
+            false => self.aux_tables.get_mut(table),
+        }
+    }
+
+    /// Applies a patch to this file.
+    ///
+    /// If any of the patch's conditions isn't met (a string doesn't have its expected original
+    /// value, or a table to add already exists), the error describing it is returned and the file
+    /// is left unchanged.
+    pub fn apply_patch(&mut self, patch: &GXTPatch) -> Result<(), GXTError> {
+
+        let mut patched = self.clone();
+        let format = self.format;
+
+        for t in &patch.add_tables {
+            if t == MAIN_TABLE_NAME || patched.aux_tables.contains_key(t) {
+                return Err(GXTError::PatchTableExists { table: t.clone() });
+            }
+            patched.aux_tables.insert(t.clone(), IndexMap::new());
+        }
+
+        for r in &patch.rename_tables {
+            if r.to == MAIN_TABLE_NAME || patched.aux_tables.contains_key(&r.to) {
+                return Err(GXTError::PatchTableExists { table: r.to.clone() });
+            }
+            let Some(strings) = patched.aux_tables.shift_remove(&r.from) else {
+                return Err(GXTError::PatchNotFound { table: r.from.clone(), key: None });
+            };
+            patched.aux_tables.insert(r.to.clone(), strings);
+        }
+
+        for s in &patch.set {
+            let Some(table) = patched.table_mut(&s.table) else {
+                return Err(GXTError::PatchNotFound { table: s.table.clone(), key: None });
+            };
+            let existing = find_key(table, &s.key, &format);
+
+            if let Some(original) = &s.original {
+                let found = existing.as_ref().map(|k| table[k].clone());
+                if found.as_ref() != Some(original) {
+                    return Err(GXTError::PatchMismatch { table: s.table.clone(), key: s.key.clone(), expected: original.clone(), found });
+                }
+            }
+
+            match existing {
+                Some(k) => { table[&k] = s.value.clone(); },
+                None => { table.insert(s.key.clone(), s.value.clone()); },
+            }
+        }
+
+        for d in &patch.delete {
+            let Some(table) = patched.table_mut(&d.table) else {
+                return Err(GXTError::PatchNotFound { table: d.table.clone(), key: None });
+            };
+            let Some(existing) = find_key(table, &d.key, &format) else {
+                return Err(GXTError::PatchNotFound { table: d.table.clone(), key: Some(d.key.clone()) });
+            };
+
+            if let Some(original) = &d.original && table[&existing] != *original {
+                return Err(GXTError::PatchMismatch { table: d.table.clone(), key: d.key.clone(), expected: original.clone(), found: Some(table[&existing].clone()) });
+            }
+            table.shift_remove(&existing);
+        }
+
+        for t in &patch.remove_tables {
+            if patched.aux_tables.shift_remove(t).is_none() {
+                return Err(GXTError::PatchNotFound { table: t.clone(), key: None });
+            }
+        }
+
+        *self = patched;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::diff;
+    use crate::string_to_name_crc32;
+    use super::*;
+
+    #[test]
+    fn patch_from_diff_test() {
+
+        let old = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hello".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+                ("OLD".to_string(),"Old".to_string()),
+            ]),
+            IndexMap::from([
+                ("AUX1".to_string(), IndexMap::from([("A".to_string(),"A".to_string()), ("U".to_string(),"Unchanged".to_string())])),
+                ("GONE".to_string(), IndexMap::from([("G".to_string(),"G".to_string())])),
+            ]),
+            );
+        let new = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hi".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+                ("NEW".to_string(),"New".to_string()),
+            ]),
+            IndexMap::from([
+                ("AUX2".to_string(), IndexMap::from([("A".to_string(),"A!".to_string()), ("U".to_string(),"Unchanged".to_string())])),
+                ("ADDED".to_string(), IndexMap::from([("D".to_string(),"D".to_string())])),
+            ]),
+            );
+
+        let patch = GXTPatch::from_diff(&diff(&old, &new, &None));
+
+        // AUX1 was renamed to AUX2, and its unchanged string moves along with it
+        assert!( patch.rename_tables == vec!(GXTPatchRename { from: "AUX1".to_string(), to: "AUX2".to_string() }) );
+        assert!( patch.set.iter().all(|s| s.key != "U") );
+
+        // the patch survives being written and read back
+        let mut text: Vec<u8> = vec!();
+        patch.write_to_text(&mut text).unwrap();
+        let patch = read_patch(&mut std::io::Cursor::new(&text)).unwrap();
+
+        let mut patched = old.clone();
+        patched.apply_patch(&patch).unwrap();
+        assert!( diff(&patched, &new, &None).is_empty() );
+
+        // applying it a second time fails, as the original strings have changed
+        let Err(e) = patched.apply_patch(&patch) else { panic!("the patch was already applied"); };
+        assert!( matches!(e, GXTError::PatchTableExists { .. } | GXTError::PatchMismatch { .. }) );
+        assert!( diff(&patched, &new, &None).is_empty() );
+    }
+
+    #[test]
+    fn apply_patch_test() {
+
+        let hello_hash = format!("#{:08X}", string_to_name_crc32("HELLO").unwrap());
+        let x = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([(hello_hash.clone(),"Hello".to_string())]),
+            IndexMap::new(),
+            );
+
+        // the patch refers to a string by its name, the file by its hash
+        let patch = GXTPatch {
+            set: vec!(GXTPatchSet { table: "MAIN".to_string(), key: "HELLO".to_string(), value: "Hi".to_string(), original: Some("Hello".to_string()) }),
+            ..Default::default()
+        };
+        let mut y = x.clone();
+        y.apply_patch(&patch).unwrap();
+        assert!( y.main_table.len() == 1 && y.main_table[&hello_hash] == "Hi" );
+
+        let patch = GXTPatch {
+            set: vec!(GXTPatchSet { table: "MAIN".to_string(), key: "HELLO".to_string(), value: "Hi".to_string(), original: Some("Good day".to_string()) }),
+            ..Default::default()
+        };
+        let mut y = x.clone();
+        let Err(e) = y.apply_patch(&patch) else { panic!("the original doesn't match"); };
+        assert!( e.to_string() == "GXT patch error in [MAIN] HELLO: expected \"Good day\", found \"Hello\"" );
+        assert!( y == x );
+
+        let patch = GXTPatch {
+            delete: vec!(GXTPatchDelete { table: "MAIN".to_string(), key: "BYE".to_string(), original: None }),
+            ..Default::default()
+        };
+        let Err(e) = y.apply_patch(&patch) else { panic!("BYE doesn't exist"); };
+        assert!( e.to_string() == "GXT patch error in [MAIN] BYE: the string does not exist" );
+    }
+}
@@ -0,0 +1,173 @@
+//! This module is used to handle the whole set of GXT files of a game, one for each language.
+//!
+//! A game ships a GXT file for every language it supports (`american.gxt`, `french.gxt` and so
+//! on), and the game expects them all to have the same tables and strings. A [GXTProject] holds
+//! one [GXTFile] per language, each with its own character table, and can check that they stay
+//! in sync ([GXTProject::check_parity]) and compile all of them at once
+//! ([GXTProject::compile_all]).
+//!
+//! The languages of a project and the files they are read from and compiled into are listed in a
+//! [GXTProjectManifest], which can be read from a TOML file:
+//!
+//! ```toml
+//! [languages.american]
+//! source = "text/american.toml"
+//! output = "TEXT/american.gxt"
+//!
+//! [languages.polish]
+//! source = "text/polish.toml"
+//! output = "TEXT/polish.gxt"
+//! character_table = "tables/polish.toml"
+//! ```
+//!
+//! The first language listed is the *reference* language that the others are compared with.
+//!
+use indexmap::IndexMap;
+use std::fmt;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use crate::{GXTCharacterTable, GXTError, GXTFile, WriteOptions};
+use crate::consistency::{GXTConsistencyIssue, GXTConsistencyKind, check_translation};
+use crate::validation::GXTValidationIssue;
+use crate::parse_toml;
+
+/// Where the text of a language comes from, and where it is compiled to.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GXTLanguageManifest {
+    /// The text (or GXT) file the language's strings are read from.
+    pub source: PathBuf,
+    /// The GXT file the language is compiled into.
+    pub output: PathBuf,
+    /// A custom character table for the language, if it needs one.
+    #[serde(default)]
+    pub character_table: Option<PathBuf>,
+}
+
+/// Lists the languages of a project. Paths are usually relative to the manifest's own location.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GXTProjectManifest {
+    /// Every language of the project, starting with the reference language.
+    pub languages: IndexMap<String, GXTLanguageManifest>,
+}
+
+/// Read a project manifest from a TOML file.
+pub fn read_project_manifest(file: &mut (impl Read + std::io::Seek)) -> Result<GXTProjectManifest,GXTError> {
+
+    let mut raw_data: String = Default::default();
+    file.read_to_string(&mut raw_data)?;
+
+    let manifest: GXTProjectManifest = parse_toml(&raw_data)?;
+    Ok(manifest)
+}
+
+/// A single language of a project.
+pub struct GXTLanguage {
+    /// The language's strings.
+    pub file: GXTFile,
+    /// The character table used to compile the language, if it needs a custom one.
+    pub custom_table: Option<GXTCharacterTable>,
+}
+
+/// A string or table that a language has, but the reference language doesn't (or the other way
+/// around).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTParityIssue {
+    /// The language that differs from the reference language.
+    pub language: String,
+    /// The difference itself.
+    pub issue: GXTConsistencyIssue,
+}
+
+impl fmt::Display for GXTParityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.language, self.issue)
+    }
+}
+
+/// A set of GXT files for the same game, one for each language.
+#[derive(Default)]
+pub struct GXTProject {
+    /// Every language of the project, starting with the reference language.
+    pub languages: IndexMap<String, GXTLanguage>,
+}
+
+impl GXTProject {
+    /// Compares every language with the reference (first) language, returning the tables and
+    /// strings that only exist in one of them. Placeholders and tags are not compared; use
+    /// [check_translation] for that.
+    pub fn check_parity(&self) -> Vec<GXTParityIssue> {
+
+        let Some((_, reference)) = self.languages.first() else {
+            return vec!();
+        };
+
+        self.languages.iter().skip(1).flat_map(|(language, l)| {
+            check_translation(&reference.file, &l.file).into_iter()
+                .filter(|i| !matches!(i.kind, GXTConsistencyKind::TagMismatch { .. }))
+                .map(|issue| GXTParityIssue { language: language.clone(), issue })
+        }).collect()
+    }
+
+    /// Checks whether every language can be compiled with the specified [WriteOptions] (using its
+    /// own character table instead of the one in the options), returning every problem found
+    /// along with the language it was found in. Only errors ([GXTValidationIssue::is_error]) stop
+    /// a language from compiling.
+    pub fn validate(&self, options: &WriteOptions) -> Vec<(String, GXTValidationIssue)> {
+
+        self.languages.iter().flat_map(|(language, l)| {
+            let options = WriteOptions { custom_table: &l.custom_table, ..options.clone() };
+            l.file.validate(&options).into_iter().map(|i| (language.clone(), i))
+        }).collect()
+    }
+
+    /// Compiles every language with the specified [WriteOptions] (using its own character table
+    /// instead of the one in the options), returning the contents of each GXT file, or the error
+    /// that stopped it from compiling. A language failing to compile doesn't stop the others.
+    pub fn compile_all(&self, options: &WriteOptions) -> IndexMap<String, Result<Vec<u8>, GXTError>> {
+
+        self.languages.iter().map(|(language, l)| {
+            let options = WriteOptions { custom_table: &l.custom_table, ..options.clone() };
+            let mut data: Vec<u8> = vec!();
+            let res = l.file.write_to_gxt_with_options(&mut data, &options).map(|_| data);
+            (language.clone(), res)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GXTFileFormat;
+    use super::*;
+
+    #[test]
+    fn project_test() {
+
+        let manifest = read_project_manifest(&mut std::io::Cursor::new(
+            "[languages.american]\nsource = \"american.toml\"\noutput = \"TEXT/american.gxt\"\n\n\
+            [languages.polish]\nsource = \"polish.toml\"\noutput = \"TEXT/polish.gxt\"\ncharacter_table = \"polish_table.toml\"\n"
+            )).unwrap();
+        assert!( manifest.languages.keys().eq(["american", "polish"]) );
+        assert!( manifest.languages["polish"].character_table == Some(PathBuf::from("polish_table.toml")) );
+
+        let file = |strings: &[(&str, &str)]| GXTFile::new(
+            GXTFileFormat::Vice,
+            strings.iter().map(|(k,v)| (k.to_string(), v.to_string())).collect(),
+            IndexMap::new(),
+            );
+
+        let mut project = GXTProject::default();
+        project.languages.insert("american".to_string(), GXTLanguage { file: file(&[("HELLO","Hello"), ("BYE","Bye")]), custom_table: None });
+        project.languages.insert("polish".to_string(), GXTLanguage { file: file(&[("HELLO","Cześć"), ("NEW","Nowy")]), custom_table: None });
+
+        assert!( project.check_parity().iter().map(|i| i.to_string()).collect::<Vec<_>>() == vec!(
+            "polish: [MAIN] BYE: string is missing from the translation",
+            "polish: [MAIN] NEW: string does not exist in the source",
+        ));
+
+        // 'ś' and 'ć' can't be encoded in VC without a custom table, but the other language still compiles
+        let issues = project.validate(&Default::default());
+        assert!( issues.len() == 2 && issues.iter().all(|(l, i)| l == "polish" && i.key.as_deref() == Some("HELLO")) );
+        let compiled = project.compile_all(&Default::default());
+        assert!( compiled["american"].is_ok() && compiled["polish"].is_err() );
+    }
+}
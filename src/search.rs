@@ -0,0 +1,200 @@
+//! This module is used to find strings across all tables of a GXT file, and to replace text in
+//! them.
+//!
+//! A [GXTSearch] selects strings by regular expressions on their names and values, optionally
+//! limited to some tables. [GXTFile::search] lists the strings it selects, along with the
+//! positions of every match in their values, and [GXTFile::replace] replaces those matches.
+//!
+//! Replacing text in GXT strings can easily break their tags: replacing `b` with `p` would turn
+//! every `~b~` (blue) into `~p~` (purple). Because of that, [GXTFile::replace] can leave tags
+//! alone and only replace text outside of them, using [crate::tags::tokenize] to tell them apart.
+//! A match can then only be found within a single piece of text between two tags.
+//!
+use regex::Regex;
+use std::fmt;
+use std::ops::Range;
+use crate::GXTFile;
+use crate::tags::{GXTToken, tokenize};
+
+/// Selects strings in a file. A string is selected if it is in one of the tables, and its name and
+/// value match the respective patterns. Criteria that are left empty select every string.
+#[derive(Clone, Debug, Default)]
+pub struct GXTSearch {
+    /// A pattern that the string's name must match.
+    pub key: Option<Regex>,
+    /// A pattern that the string's value must match.
+    pub value: Option<Regex>,
+    /// The tables to search (the main table is called [crate::MAIN_TABLE_NAME]). If empty, every
+    /// table is searched.
+    pub tables: Vec<String>,
+}
+
+impl GXTSearch {
+    // true if the string is selected
+    fn selects(&self, table: &str, key: &str, value: &str) -> bool {
+        (self.tables.is_empty() || self.tables.iter().any(|t| t == table))
+            && self.key.as_ref().is_none_or(|r| r.is_match(key))
+            && self.value.as_ref().is_none_or(|r| r.is_match(value))
+    }
+}
+
+/// A string selected by a [GXTSearch].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTSearchMatch {
+    /// The name of the table.
+    pub table: String,
+    /// The name of the string.
+    pub key: String,
+    /// The string's value.
+    pub value: String,
+    /// The byte ranges of the value that match the value pattern (empty if there isn't one).
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl fmt::Display for GXTSearchMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {} = {:?}", self.table, self.key, self.value)
+    }
+}
+
+/// A string changed by [GXTFile::replace].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTReplacement {
+    /// The name of the table.
+    pub table: String,
+    /// The name of the string.
+    pub key: String,
+    /// The value before the replacement.
+    pub old: String,
+    /// The value after the replacement.
+    pub new: String,
+}
+
+impl fmt::Display for GXTReplacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {:?} -> {:?}", self.table, self.key, self.old, self.new)
+    }
+}
+
+impl GXTFile {
+    /// Returns every string selected by the search, in the order of the file's tables.
+    pub fn search(&self, search: &GXTSearch) -> Vec<GXTSearchMatch> {
+
+        let mut res: Vec<GXTSearchMatch> = vec!();
+
+        for (table, strings) in self.tables() {
+            for (key, value) in strings.iter().filter(|(k,v)| search.selects(table, k, v)) {
+                res.push(GXTSearchMatch {
+                    table: table.to_string(),
+                    key: key.clone(),
+                    value: value.clone(),
+                    ranges: match &search.value {
+                        Some(r) => r.find_iter(value).map(|m| m.range()).collect(),
+                        None => vec!(),
+                    },
+                });
+            }
+        }
+
+        res
+    }
+
+    /// Replaces every match of the search's value pattern in the strings it selects, returning the
+    /// strings that were changed. The replacement may refer to capture groups, as described in
+    /// [Regex::replace_all]. If the search has no value pattern, nothing is replaced.
+    ///
+    /// If `skip_tags` is true, tags are left as they are, and only text outside of them is
+    /// replaced.
+    pub fn replace(&mut self, search: &GXTSearch, replacement: &str, skip_tags: bool) -> Vec<GXTReplacement> {
+
+        let mut res: Vec<GXTReplacement> = vec!();
+        let Some(pattern) = &search.value else {
+            return res;
+        };
+        let format = self.format;
+
+        let tables = std::iter::once((crate::MAIN_TABLE_NAME, &mut self.main_table))
+            .chain(self.aux_tables.iter_mut().map(|(k,v)| (k.as_str(), v)));
+
+        for (table, strings) in tables {
+            for (key, value) in strings.iter_mut().filter(|(k,v)| search.selects(table, k, v)) {
+
+                let new = match skip_tags {
+                    true => tokenize(value, &format).iter().map(|t| match t {
+                        GXTToken::Text(s) => pattern.replace_all(s, replacement).into_owned(),
+                        t => t.to_string(),
+                    }).collect(),
+                    false => pattern.replace_all(value, replacement).into_owned(),
+                };
+
+                if new != *value {
+                    res.push(GXTReplacement { table: table.to_string(), key: key.clone(), old: value.clone(), new: new.clone() });
+                    *value = new;
+                }
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+    use crate::GXTFileFormat;
+    use super::*;
+
+    fn file() -> GXTFile {
+        GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([
+                ("TOMMY".to_string(),"Tommy, get ~b~blue~w~ cars".to_string()),
+                ("KEN".to_string(),"Ken".to_string()),
+            ]),
+            IndexMap::from([("MISS1".to_string(), IndexMap::from([("M1_A".to_string(),"Tommy! Go to the ~y~bar~w~.".to_string())]))]),
+            )
+    }
+
+    #[test]
+    fn search_test() {
+
+        let x = file();
+        let found = x.search(&GXTSearch { value: Some(Regex::new("Tommy").unwrap()), ..Default::default() });
+        assert!( found.iter().map(|m| m.to_string()).collect::<Vec<_>>() == vec!(
+            "[MAIN] TOMMY = \"Tommy, get ~b~blue~w~ cars\"",
+            "[MISS1] M1_A = \"Tommy! Go to the ~y~bar~w~.\"",
+        ));
+        assert!( found[0].ranges == vec!(Range { start: 0, end: 5 }) );
+
+        let found = x.search(&GXTSearch { value: Some(Regex::new("Tommy").unwrap()), tables: vec!("MISS1".to_string()), ..Default::default() });
+        assert!( found.len() == 1 && found[0].key == "M1_A" );
+
+        let found = x.search(&GXTSearch { key: Some(Regex::new("^K").unwrap()), ..Default::default() });
+        assert!( found.len() == 1 && found[0].key == "KEN" && found[0].ranges.is_empty() );
+    }
+
+    #[test]
+    fn replace_test() {
+
+        // tags are left alone, even though they match
+        let mut x = file();
+        let replaced = x.replace(&GXTSearch { value: Some(Regex::new("b").unwrap()), ..Default::default() }, "p", true);
+        assert!( x.main_table["TOMMY"] == "Tommy, get ~b~plue~w~ cars" && x.aux_tables["MISS1"]["M1_A"] == "Tommy! Go to the ~y~par~w~." );
+        assert!( replaced.len() == 2 );
+
+        let mut x = file();
+        x.replace(&GXTSearch { value: Some(Regex::new("b").unwrap()), ..Default::default() }, "p", false);
+        assert!( x.main_table["TOMMY"] == "Tommy, get ~p~plue~w~ cars" );
+
+        // capture groups and table filters
+        let mut x = file();
+        let replaced = x.replace(&GXTSearch { value: Some(Regex::new("(T)ommy").unwrap()), tables: vec!("MAIN".to_string()), ..Default::default() }, "${1}ommy Vercetti", true);
+        assert!( replaced == vec!(GXTReplacement {
+            table: "MAIN".to_string(),
+            key: "TOMMY".to_string(),
+            old: "Tommy, get ~b~blue~w~ cars".to_string(),
+            new: "Tommy Vercetti, get ~b~blue~w~ cars".to_string(),
+        }));
+        assert!( x.aux_tables["MISS1"]["M1_A"].starts_with("Tommy!") );
+    }
+}
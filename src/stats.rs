@@ -0,0 +1,188 @@
+//! This module is used to measure the contents of a GXT file, and the progress of its
+//! translation.
+//!
+//! [stats] counts the strings, words and characters in every table of a file. Only the text is
+//! counted, not the tags, so `~r~Hello~s~` is one word of five characters (a line break counts
+//! as a space). It also counts how often each character occurs, which shows the glyphs a font
+//! for the file needs to have.
+//!
+//! When a source file (usually the English one) is given, [stats] also counts the strings of a
+//! translation that are still identical to the source, and the strings of the source that are
+//! missing from the translation.
+//!
+use indexmap::IndexMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use crate::{GXTFile, GXTFileFormat};
+use crate::diff::{GXTKeyId, key_id};
+use crate::tags::{GXTToken, tokenize};
+
+/// Statistics of a single table.
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GXTTableStats {
+    /// The name of the table (the main table is called [crate::MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The number of strings.
+    pub strings: usize,
+    /// The number of words outside of tags.
+    pub words: usize,
+    /// The number of characters outside of tags.
+    pub characters: usize,
+    /// The number of strings with the same value as in the source file, or None without one.
+    pub identical: Option<usize>,
+    /// The number of strings of the source file that this file doesn't have, or None without one.
+    pub missing: Option<usize>,
+    /// How many times each character occurs outside of tags.
+    pub character_frequency: BTreeMap<char, usize>,
+}
+
+/// Statistics of a whole file, as returned by [stats].
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GXTStats {
+    /// Every table of the file, followed by tables that only exist in the source file.
+    pub tables: Vec<GXTTableStats>,
+}
+
+impl GXTStats {
+    /// Returns the sums of the statistics of all tables, under the name `TOTAL`.
+    pub fn total(&self) -> GXTTableStats {
+
+        let sum = |f: fn(&GXTTableStats) -> Option<usize>| self.tables.iter().map(f).sum::<Option<usize>>();
+
+        let mut res = GXTTableStats {
+            table: "TOTAL".to_string(),
+            strings: self.tables.iter().map(|t| t.strings).sum(),
+            words: self.tables.iter().map(|t| t.words).sum(),
+            characters: self.tables.iter().map(|t| t.characters).sum(),
+            identical: sum(|t| t.identical),
+            missing: sum(|t| t.missing),
+            character_frequency: BTreeMap::new(),
+        };
+        for (c, count) in self.tables.iter().flat_map(|t| &t.character_frequency) {
+            *res.character_frequency.entry(*c).or_default() += count;
+        }
+        res
+    }
+}
+
+impl fmt::Display for GXTTableStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {} strings, {} words, {} characters", self.table, self.strings, self.words, self.characters)?;
+        if let Some(i) = self.identical { write!(f, ", {i} identical to the source")?; }
+        if let Some(m) = self.missing { write!(f, ", {m} missing")?; }
+        Ok(())
+    }
+}
+
+// counts the words and characters of a string, outside of its tags
+fn count_string(string: &str, format: &GXTFileFormat, res: &mut GXTTableStats) {
+
+    // line breaks separate words, while other tags (mostly colors) may be in the middle of one
+    let mut text = String::new();
+    for t in tokenize(string, format) {
+        match t {
+            GXTToken::Text(s) => { text.push_str(&s); },
+            GXTToken::NewLine => { text.push(' '); },
+            _ => {},
+        }
+    }
+
+    res.words += text.split_whitespace().count();
+    for c in text.chars() {
+        res.characters += 1;
+        *res.character_frequency.entry(c).or_default() += 1;
+    }
+}
+
+/// Counts the strings, words and characters in every table of a file. If a source file is given,
+/// also counts the strings that are identical to the source or missing from the file.
+///
+/// In GTA SA files, strings are matched with the source by the CRC32 hash of their names, the same
+/// way as in [crate::diff::diff].
+pub fn stats(file: &GXTFile, source: Option<&GXTFile>) -> GXTStats {
+
+    let is_san = |f: &GXTFileFormat| matches!(f, GXTFileFormat::San8 | GXTFileFormat::San16);
+    let hashed = is_san(&file.format) || source.is_some_and(|s| is_san(&s.format));
+
+    let source_tables: IndexMap<&str, HashMap<GXTKeyId, &String>> = match source {
+        Some(s) => s.tables().map(|(t, strings)| (t, strings.iter().map(|(k,v)| (key_id(k, hashed), v)).collect())).collect(),
+        None => IndexMap::new(),
+    };
+
+    let mut res = GXTStats::default();
+
+    for (table, strings) in file.tables() {
+
+        let mut table_stats = GXTTableStats { table: table.to_string(), strings: strings.len(), ..Default::default() };
+        for v in strings.values() {
+            count_string(v, &file.format, &mut table_stats);
+        }
+
+        if source.is_some() {
+            let empty = HashMap::new();
+            let source_strings = source_tables.get(table).unwrap_or(&empty);
+            let ids: HashMap<GXTKeyId, &String> = strings.iter().map(|(k,v)| (key_id(k, hashed), v)).collect();
+
+            table_stats.identical = Some(ids.iter().filter(|(id, v)| source_strings.get(*id) == Some(*v)).count());
+            table_stats.missing = Some(source_strings.keys().filter(|id| !ids.contains_key(*id)).count());
+        }
+
+        res.tables.push(table_stats);
+    }
+
+    // tables that haven't been translated at all
+    let tables: Vec<&str> = file.tables().map(|(t,_)| t).collect();
+    for (table, source_strings) in source_tables.iter().filter(|(t,_)| !tables.contains(*t)) {
+        res.tables.push(GXTTableStats { table: table.to_string(), identical: Some(0), missing: Some(source_strings.len()), ..Default::default() });
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_test() {
+
+        let source = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hello ~r~world~s~!".to_string()),
+                ("BYE".to_string(),"Bye".to_string()),
+                ("OK".to_string(),"OK".to_string()),
+            ]),
+            IndexMap::from([("AUX1".to_string(), IndexMap::from([("A".to_string(),"A".to_string())]))]),
+            );
+
+        let x = stats(&source, None);
+        assert!( x.tables[0] == GXTTableStats {
+            table: "MAIN".to_string(),
+            strings: 3,
+            words: 4,
+            characters: 17,
+            identical: None,
+            missing: None,
+            character_frequency: "Hello world!ByeOK".chars().fold(BTreeMap::new(), |mut m, c| { *m.entry(c).or_default() += 1; m }),
+        });
+        assert!( x.tables[0].character_frequency[&'l'] == 3 );
+
+        // the translation refers to one of the strings by its hash
+        let translation = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("HELLO".to_string(),"Hallo ~r~Welt~s~!".to_string()),
+                (format!("#{:08X}", crate::string_to_name_crc32("OK").unwrap()),"OK".to_string()),
+            ]),
+            IndexMap::new(),
+            );
+
+        let x = stats(&translation, Some(&source));
+        assert!( x.tables.iter().map(|t| t.to_string()).collect::<Vec<_>>() == vec!(
+            "[MAIN] 2 strings, 3 words, 13 characters, 1 identical to the source, 1 missing",
+            "[AUX1] 0 strings, 0 words, 0 characters, 0 identical to the source, 1 missing",
+        ));
+        assert!( x.total().to_string() == "[TOTAL] 2 strings, 3 words, 13 characters, 1 identical to the source, 2 missing" );
+    }
+}
@@ -0,0 +1,361 @@
+//! This module is used to split GXT strings into text and tilde-based tags, like `~r~` (which
+//! switches the text's color to red) or `~k~~PED_FIREWEAPON~` (which is replaced with the key
+//! bound to a specific action).
+//!
+//! The meaning of most tags depends on the game: `~o~` is an orange color in Vice City, but a
+//! button icon in San Andreas. [tokenize] takes that into account, returning a list of
+//! [GXTToken]s with the tags already classified according to the file's [GXTFileFormat]. Every
+//! token remembers its original text, so [join_tokens] (or simply formatting each token) gives
+//! back the exact string that was tokenized.
+//!
+//! Tildes are paired up from the start of the string, the same way the rest of the crate treats
+//! them when decoding and encoding strings. A final unpaired tilde starts an
+//! [GXTToken::UnclosedTag], which spans the rest of the string.
+//!
+use std::fmt;
+use crate::GXTFileFormat;
+
+/// A text color that can be selected using a tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GXTColor {
+    /// The default text color of the current text box (`~s~` in San Andreas).
+    Default,
+    /// Black (`~l~`).
+    Black,
+    /// Blue (`~b~`).
+    Blue,
+    /// Green (`~g~` in III and San Andreas, `~t~` in Vice City).
+    Green,
+    /// Highlighted white (`~h~`).
+    Highlight,
+    /// Light blue (`~x~` in Vice City).
+    LightBlue,
+    /// Orange (`~o~` in Vice City).
+    Orange,
+    /// Pink (`~g~` in Vice City).
+    Pink,
+    /// Purple (`~p~` in Vice City and San Andreas).
+    Purple,
+    /// Red (`~r~`).
+    Red,
+    /// White (`~w~`).
+    White,
+    /// Yellow (`~y~`).
+    Yellow,
+}
+
+/// A controller button that can be shown as an icon using a tag (San Andreas only).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GXTButton {
+    /// Clicking the left analog stick (`~A~`).
+    LeftStickClick,
+    /// Clicking the right analog stick (`~c~`).
+    RightStickClick,
+    /// The left trigger (`~K~`).
+    LeftTrigger,
+    /// The right trigger (`~j~`).
+    RightTrigger,
+    /// The left bumper, or the white button on Xbox (`~m~`).
+    LeftBumper,
+    /// The right bumper, or the black button on Xbox (`~v~`).
+    RightBumper,
+    /// The top face button (`~t~`).
+    FaceTop,
+    /// The bottom face button (`~x~`).
+    FaceBottom,
+    /// The left face button (`~q~`).
+    FaceLeft,
+    /// The right face button (`~o~`).
+    FaceRight,
+    /// Up on the d-pad (`~u~`).
+    DPadUp,
+    /// Down on the d-pad (`~d~`).
+    DPadDown,
+    /// Left on the d-pad (`~<~`).
+    DPadLeft,
+    /// Right on the d-pad (`~>~`).
+    DPadRight,
+}
+
+/// A single piece of a GXT string: either a run of regular text or one tag.
+///
+/// Tags keep their original contents (the part between the tildes), so that the string can be put
+/// back together exactly as it was.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GXTToken {
+    /// Regular text, without any tildes.
+    Text(String),
+    /// A tag that changes the color of the following text.
+    Color {
+        /// The tag's contents.
+        tag: String,
+        /// The color it selects.
+        color: GXTColor,
+    },
+    /// A tag that shows a controller button icon.
+    Button {
+        /// The tag's contents.
+        tag: String,
+        /// The button it shows.
+        button: GXTButton,
+    },
+    /// A line break (`~n~`).
+    NewLine,
+    /// A placeholder for a number that the game fills in (`~1~`).
+    Number(String),
+    /// A placeholder for the key bound to an action (`~k~~PED_FIREWEAPON~`). The action name is
+    /// None if the `~k~` tag isn't immediately followed by another tag.
+    KeyBinding(Option<String>),
+    /// A marker for subtitles, which are hidden if subtitles are disabled (`~z~`, San Andreas
+    /// only).
+    Subtitle,
+    /// A tag that isn't known for the format. Empty tags (`~~`) are also reported this way.
+    Unknown(String),
+    /// A final tilde that has no pair, followed by the rest of the string.
+    UnclosedTag(String),
+}
+
+impl GXTToken {
+    /// Returns true if this token is a tag of any kind (including unknown or unclosed ones), and
+    /// false if it's regular text.
+    pub fn is_tag(&self) -> bool {
+        !matches!(self, GXTToken::Text(_))
+    }
+}
+
+impl fmt::Display for GXTToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GXTToken::Text(s) => write!(f, "{s}"),
+            GXTToken::Color { tag, .. } | GXTToken::Button { tag, .. } => write!(f, "~{tag}~"),
+            GXTToken::NewLine => write!(f, "~n~"),
+            GXTToken::Number(n) => write!(f, "~{n}~"),
+            GXTToken::KeyBinding(None) => write!(f, "~k~"),
+            GXTToken::KeyBinding(Some(action)) => write!(f, "~k~~{action}~"),
+            GXTToken::Subtitle => write!(f, "~z~"),
+            GXTToken::Unknown(t) => write!(f, "~{t}~"),
+            GXTToken::UnclosedTag(s) => write!(f, "~{s}"),
+        }
+    }
+}
+
+/// Returns the color a tag selects in the specified format, if it is a color tag.
+pub fn tag_color(tag: &str, format: &GXTFileFormat) -> Option<GXTColor> {
+    match format {
+        GXTFileFormat::Three => match tag {
+            "b" => Some(GXTColor::Blue),
+            "g" => Some(GXTColor::Green),
+            "h" => Some(GXTColor::Highlight),
+            "l" => Some(GXTColor::Black),
+            "r" => Some(GXTColor::Red),
+            "w" => Some(GXTColor::White),
+            "y" => Some(GXTColor::Yellow),
+            _ => None,
+        },
+        GXTFileFormat::Vice => match tag {
+            "b" => Some(GXTColor::Blue),
+            "g" => Some(GXTColor::Pink),
+            "h" => Some(GXTColor::Highlight),
+            "l" => Some(GXTColor::Black),
+            "o" => Some(GXTColor::Orange),
+            "p" => Some(GXTColor::Purple),
+            "r" => Some(GXTColor::Red),
+            "t" => Some(GXTColor::Green),
+            "w" => Some(GXTColor::White),
+            "x" => Some(GXTColor::LightBlue),
+            "y" => Some(GXTColor::Yellow),
+            _ => None,
+        },
+        GXTFileFormat::San8 | GXTFileFormat::San16 => match tag {
+            "b" => Some(GXTColor::Blue),
+            "g" => Some(GXTColor::Green),
+            "h" => Some(GXTColor::Highlight),
+            "l" => Some(GXTColor::Black),
+            "p" => Some(GXTColor::Purple),
+            "r" => Some(GXTColor::Red),
+            "s" => Some(GXTColor::Default),
+            "w" => Some(GXTColor::White),
+            "y" => Some(GXTColor::Yellow),
+            _ => None,
+        },
+    }
+}
+
+/// Returns the button a tag shows in the specified format, if it is a button tag.
+pub fn tag_button(tag: &str, format: &GXTFileFormat) -> Option<GXTButton> {
+    match format {
+        GXTFileFormat::Three | GXTFileFormat::Vice => None,
+        GXTFileFormat::San8 | GXTFileFormat::San16 => match tag {
+            "A" => Some(GXTButton::LeftStickClick),
+            "c" => Some(GXTButton::RightStickClick),
+            "K" => Some(GXTButton::LeftTrigger),
+            "j" => Some(GXTButton::RightTrigger),
+            "m" => Some(GXTButton::LeftBumper),
+            "v" => Some(GXTButton::RightBumper),
+            "t" => Some(GXTButton::FaceTop),
+            "x" => Some(GXTButton::FaceBottom),
+            "q" => Some(GXTButton::FaceLeft),
+            "o" => Some(GXTButton::FaceRight),
+            "u" => Some(GXTButton::DPadUp),
+            "d" => Some(GXTButton::DPadDown),
+            "<" => Some(GXTButton::DPadLeft),
+            ">" => Some(GXTButton::DPadRight),
+            _ => None,
+        },
+    }
+}
+
+/// Names of the controller actions that can follow a `~k~` tag. This is the combined list of
+/// actions from GTA III, Vice City and San Andreas, as the games themselves silently show nothing
+/// for a name they don't know.
+pub const KNOWN_ACTIONS: &[&str] = &[
+    "PED_FIREWEAPON", "PED_FIREWEAPON_ALT", "PED_CYCLE_WEAPON_RIGHT", "PED_CYCLE_WEAPON_LEFT",
+    "GO_FORWARD", "GO_BACK", "GO_LEFT", "GO_RIGHT", "PED_SNIPER_ZOOM_IN", "PED_SNIPER_ZOOM_OUT",
+    "VEHICLE_ENTER_EXIT", "CAMERA_CHANGE_VIEW_ALL_SITUATIONS", "PED_JUMPING", "PED_SPRINT",
+    "PED_LOOKBEHIND", "PED_DUCK", "PED_ANSWER_PHONE", "SNEAK_ABOUT",
+    "VEHICLE_FIREWEAPON", "VEHICLE_FIREWEAPON_ALT", "VEHICLE_STEERLEFT", "VEHICLE_STEERRIGHT",
+    "VEHICLE_STEERUP", "VEHICLE_STEERDOWN", "VEHICLE_ACCELERATE", "VEHICLE_BRAKE",
+    "VEHICLE_CHANGE_RADIO_STATION", "VEHICLE_RADIO_STATION_UP", "VEHICLE_RADIO_STATION_DOWN",
+    "VEHICLE_RADIO_TRACK_SKIP", "VEHICLE_HORN", "TOGGLE_SUBMISSIONS", "VEHICLE_HANDBRAKE",
+    "PED_1RST_PERSON_LOOK_LEFT", "PED_1RST_PERSON_LOOK_RIGHT", "PED_1RST_PERSON_LOOK_UP",
+    "PED_1RST_PERSON_LOOK_DOWN", "VEHICLE_LOOKLEFT", "VEHICLE_LOOKRIGHT", "VEHICLE_LOOKBEHIND",
+    "VEHICLE_MOUSELOOK", "VEHICLE_TURRETLEFT", "VEHICLE_TURRETRIGHT", "VEHICLE_TURRETUP",
+    "VEHICLE_TURRETDOWN", "PED_CYCLE_TARGET_LEFT", "PED_CYCLE_TARGET_RIGHT",
+    "PED_CENTER_CAMERA_BEHIND_PLAYER", "PED_LOCK_TARGET", "NETWORK_TALK", "CONVERSATION_YES",
+    "CONVERSATION_NO", "GROUP_CONTROL_FWD", "GROUP_CONTROL_BWD", "TOGGLE_DPAD",
+    "SWITCH_DEBUG_CAM_ON", "TAKE_SCREEN_SHOT", "SHOW_MOUSE_POINTER_TOGGLE",
+];
+
+/// Returns true if the name is one of the [KNOWN_ACTIONS].
+pub fn is_known_action(name: &str) -> bool {
+    KNOWN_ACTIONS.contains(&name)
+}
+
+// classifies the contents of a single tag, except for key bindings, which need to look at the
+// next tag as well
+fn classify_tag(tag: &str, format: &GXTFileFormat) -> GXTToken {
+
+    if let Some(color) = tag_color(tag, format) {
+        return GXTToken::Color { tag: tag.to_string(), color };
+    }
+    if let Some(button) = tag_button(tag, format) {
+        return GXTToken::Button { tag: tag.to_string(), button };
+    }
+
+    match (tag, format) {
+        ("n", _) => GXTToken::NewLine,
+        ("z", GXTFileFormat::San8 | GXTFileFormat::San16) => GXTToken::Subtitle,
+        _ if !tag.is_empty() && tag.chars().all(|c| c.is_ascii_digit()) => GXTToken::Number(tag.to_string()),
+        _ => GXTToken::Unknown(tag.to_string()),
+    }
+}
+
+/// Splits a string into text and tags, classifying the tags according to the specified format.
+pub fn tokenize(string: &str, format: &GXTFileFormat) -> Vec<GXTToken> {
+
+    // first pass: split the string into text and raw tag contents
+    let mut raw_tokens: Vec<(bool, String)> = vec!(); // (is_tag, contents)
+    let mut current = String::new();
+    let mut in_tag = false;
+
+    for c in string.chars() {
+        if c == '~' {
+            if in_tag {
+                raw_tokens.push((true, std::mem::take(&mut current)));
+            } else if !current.is_empty() {
+                raw_tokens.push((false, std::mem::take(&mut current)));
+            }
+            in_tag = !in_tag;
+        } else {
+            current.push(c);
+        }
+    }
+
+    let unclosed = if in_tag { Some(current) } else {
+        if !current.is_empty() { raw_tokens.push((false, current)); }
+        None
+    };
+
+    // second pass: classify the tags and merge key bindings with their action names
+    let mut tokens: Vec<GXTToken> = vec!();
+    let mut raw_tokens = raw_tokens.into_iter().peekable();
+
+    while let Some((is_tag, contents)) = raw_tokens.next() {
+        if !is_tag {
+            tokens.push(GXTToken::Text(contents));
+        } else if contents == "k" {
+            let action = raw_tokens.next_if(|(is_tag, _)| *is_tag).map(|(_, a)| a);
+            tokens.push(GXTToken::KeyBinding(action));
+        } else {
+            tokens.push(classify_tag(&contents, format));
+        }
+    }
+
+    if let Some(u) = unclosed {
+        tokens.push(GXTToken::UnclosedTag(u));
+    }
+
+    tokens
+}
+
+/// Puts a list of tokens back together into a string. For tokens returned by [tokenize], this
+/// gives back the original string.
+pub fn join_tokens(tokens: &[GXTToken]) -> String {
+    tokens.iter().map(|t| t.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_sa_test() {
+
+        let string = "~s~Press ~k~~PED_FIREWEAPON~ or ~o~ to shoot ~r~~1~~s~ times.~n~~z~Bye";
+        let tokens = tokenize(string, &GXTFileFormat::San8);
+
+        assert!( tokens == vec!(
+            GXTToken::Color { tag: "s".to_string(), color: GXTColor::Default },
+            GXTToken::Text("Press ".to_string()),
+            GXTToken::KeyBinding(Some("PED_FIREWEAPON".to_string())),
+            GXTToken::Text(" or ".to_string()),
+            GXTToken::Button { tag: "o".to_string(), button: GXTButton::FaceRight },
+            GXTToken::Text(" to shoot ".to_string()),
+            GXTToken::Color { tag: "r".to_string(), color: GXTColor::Red },
+            GXTToken::Number("1".to_string()),
+            GXTToken::Color { tag: "s".to_string(), color: GXTColor::Default },
+            GXTToken::Text(" times.".to_string()),
+            GXTToken::NewLine,
+            GXTToken::Subtitle,
+            GXTToken::Text("Bye".to_string()),
+        ));
+        assert!( join_tokens(&tokens) == string );
+    }
+
+    #[test]
+    fn tokenize_per_format_test() {
+
+        // ~o~ is a color in Vice City, and ~z~ means nothing there
+        let tokens = tokenize("~o~Orange~z~", &GXTFileFormat::Vice);
+        assert!( tokens[0] == GXTToken::Color { tag: "o".to_string(), color: GXTColor::Orange } );
+        assert!( tokens[2] == GXTToken::Unknown("z".to_string()) );
+    }
+
+    #[test]
+    fn tokenize_malformed_test() {
+
+        for string in ["~~empty", "~k~ alone", "unclosed ~r~tag~ here", "~", "", "a~k~"] {
+            let tokens = tokenize(string, &GXTFileFormat::Three);
+            assert!( join_tokens(&tokens) == string );
+        }
+
+        let tokens = tokenize("~~empty", &GXTFileFormat::Three);
+        assert!( tokens[0] == GXTToken::Unknown("".to_string()) );
+
+        let tokens = tokenize("unclosed ~r~tag~ here", &GXTFileFormat::Three);
+        assert!( tokens.last() == Some(&GXTToken::UnclosedTag(" here".to_string())) );
+
+        let tokens = tokenize("~k~ alone", &GXTFileFormat::Vice);
+        assert!( tokens[0] == GXTToken::KeyBinding(None) );
+    }
+}
@@ -0,0 +1,601 @@
+//! This module is used to check whether a [GXTFile] can be compiled, before actually compiling it.
+//!
+//! [GXTFile::write_to_gxt] stops at the first problem it finds, so fixing a large translation may
+//! take many attempts. [GXTFile::validate] goes through the whole file instead and returns every
+//! problem at once, as a list of [GXTValidationIssue]s pointing at the table and string that need
+//! fixing.
+//!
+//! Each issue is either an error, which makes [GXTFile::write_to_gxt_with_options] fail, or a
+//! warning about something the writer accepts, but that is likely a mistake (like an empty table)
+//! or may not work in the game (like going over one of its limits). See [GXTSeverity].
+//!
+//! A file that compiles fine may still go over the game's own limits, like the size of the buffer
+//! that mission text is loaded into. Those limits are described by [GXTLimits], which can be
+//! taken from one of the [GXTLimitProfile]s and overridden with [read_limits]. When they are set
+//! in the [WriteOptions], the validator checks them as well.
+//!
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::prelude::*;
+use crate::{GXTError, GXTFile, GXTFileFormat, GXTSubstitution, WriteOptions, MAIN_TABLE_NAME};
+use crate::{MAX_ENTRIES, MAX_STRING_LENGTH, MAX_TABLES, MAX_TOTAL_LENGTH};
+use crate::{encode_codes, parse_toml, string_to_name, string_to_name_basic};
+
+/// Limits of a game engine that a GXT file must stay within to work in the game. Each limit may
+/// be None, in which case it isn't checked.
+#[derive(serde::Serialize,serde::Deserialize,Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct GXTLimits {
+    /// The maximum number of tables, including the main table.
+    #[serde(default)]
+    pub max_tables: Option<usize>,
+    /// The maximum number of characters in a single string, as encoded (so every character of a
+    /// tag counts as well).
+    #[serde(default)]
+    pub max_string_chars: Option<usize>,
+    /// The maximum size of an auxiliary (mission) table's TDAT section, in bytes.
+    #[serde(default)]
+    pub max_mission_table_bytes: Option<u64>,
+}
+
+/// A game and platform whose [GXTLimits] are known, at least in part.
+///
+/// [GXTLimitProfile::limits] only returns limits that follow from the file format itself. The
+/// actual sizes of the games' buffers haven't been checked against the executables (or the
+/// re3, reVC and gta-reversed projects) yet, so [GXTLimitProfile::estimated_limits] only returns
+/// placeholder values for them, which have to be asked for explicitly. When targeting a specific
+/// (possibly patched) executable, set its real limits using [read_limits] and
+/// [GXTLimits::override_with].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GXTLimitProfile {
+    /// GTA III.
+    Three,
+    /// GTA: Vice City.
+    Vice,
+    /// GTA: San Andreas (PC).
+    SanPc,
+    /// GTA: San Andreas (mobile).
+    SanMobile,
+}
+
+impl GXTLimitProfile {
+    /// Returns the limits that follow from the file format: a GTA III file only has a main
+    /// table. Nothing else is set.
+    pub fn limits(&self) -> GXTLimits {
+        match self {
+            GXTLimitProfile::Three => GXTLimits { max_tables: Some(1), ..Default::default() },
+            GXTLimitProfile::Vice | GXTLimitProfile::SanPc | GXTLimitProfile::SanMobile => GXTLimits::default(),
+        }
+    }
+
+    /// Returns [GXTLimitProfile::limits], along with placeholder values for the limits of the
+    /// games' buffers. These are rough guesses that haven't been verified against the games (the
+    /// mobile values are simply twice the PC ones), so going over them doesn't necessarily mean
+    /// the file won't work. Use [read_limits] to replace them with real values.
+    pub fn estimated_limits(&self) -> GXTLimits {
+        let estimates = match self {
+            GXTLimitProfile::Three => GXTLimits { max_tables: None, max_string_chars: Some(255), max_mission_table_bytes: None },
+            GXTLimitProfile::Vice => GXTLimits { max_tables: Some(90), max_string_chars: Some(255), max_mission_table_bytes: Some(0x8000) },
+            GXTLimitProfile::SanPc => GXTLimits { max_tables: Some(200), max_string_chars: Some(400), max_mission_table_bytes: Some(0x10000) },
+            GXTLimitProfile::SanMobile => GXTLimits { max_tables: Some(200), max_string_chars: Some(400), max_mission_table_bytes: Some(0x20000) },
+        };
+        self.limits().override_with(&estimates)
+    }
+}
+
+impl GXTLimits {
+    /// Returns these limits, with every limit that is set in `other` replaced by its value.
+    pub fn override_with(&self, other: &GXTLimits) -> GXTLimits {
+        GXTLimits {
+            max_tables: other.max_tables.or(self.max_tables),
+            max_string_chars: other.max_string_chars.or(self.max_string_chars),
+            max_mission_table_bytes: other.max_mission_table_bytes.or(self.max_mission_table_bytes),
+        }
+    }
+}
+
+/// Read a set of limits from a TOML file, with the same field names as [GXTLimits]. Fields that
+/// are missing from the file are left as None.
+pub fn read_limits(file: &mut (impl Read + std::io::Seek)) -> Result<GXTLimits,GXTError> {
+
+    let mut raw_data: String = Default::default();
+    file.read_to_string(&mut raw_data)?;
+
+    let limits: GXTLimits = parse_toml(&raw_data)?;
+    Ok(limits)
+}
+
+/// How serious a [GXTValidationKind] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GXTSeverity {
+    /// The file can't be compiled.
+    Error,
+    /// The file can be compiled, but the result may not work as expected.
+    Warning,
+}
+
+/// Describes a single problem that would prevent a file from being compiled, or make the result
+/// unusable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GXTValidationKind {
+    /// The string's name is empty.
+    EmptyKey,
+    /// The string's name is longer than 8 bytes (GTA III and Vice City only).
+    KeyTooLong,
+    /// The string's name contains a character other than printable ASCII (GTA III and Vice City
+    /// only, San Andreas only stores hashes of the names).
+    InvalidKeyCharacter(char),
+    /// The string's name is a hash, which only San Andreas files can use.
+    HashedKey,
+    /// The string's name looks like a hash, but isn't a valid hexadecimal value.
+    InvalidHash,
+    /// Another string in the same table has the same name (or the same hash, in San Andreas).
+    DuplicateName(String),
+    /// The table's name is longer than 8 bytes.
+    TableNameTooLong,
+    /// The table's name contains a character other than printable ASCII.
+    InvalidTableNameCharacter(char),
+    /// An auxiliary table uses the name of the main table.
+    ReservedTableName,
+    /// The file has auxiliary tables, which GTA III files can't have.
+    AuxTableNotAllowed,
+    /// The table has no strings.
+    EmptyTable,
+    /// A character can't be encoded with the format's character table, and there is no fallback
+    /// for it. The position is counted in characters, after normalization.
+    UnencodableCharacter {
+        /// The character's position in the string.
+        position: usize,
+        /// The character itself.
+        character: char,
+    },
+    /// The table's data is too large for its size to be stored in the file.
+    TableTooLarge(u64),
+    /// The table starts too far into the file for its offset to be stored in the file.
+    FileTooLarge(u64),
+    /// The string, or the file up to this table, goes over one of the limits that
+    /// [GXTFile::read_from_gxt] enforces ([MAX_STRING_LENGTH], [MAX_TABLES], [MAX_ENTRIES] or
+    /// [MAX_TOTAL_LENGTH]), so the compiled file couldn't be read back.
+    TooLargeToRead {
+        /// What there is too much of.
+        what: &'static str,
+        /// The limit.
+        limit: usize,
+    },
+    /// The file has more tables than the game can handle (reported for the first table over the
+    /// limit).
+    TooManyTables {
+        /// The number of tables in the file.
+        count: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// The string has more characters than the game can handle.
+    StringTooLong {
+        /// The number of characters, as encoded.
+        length: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// The mission table's data is larger than the game's buffer for it.
+    MissionTableTooLarge {
+        /// The size of the table's TDAT section, in bytes.
+        size: u64,
+        /// The limit.
+        limit: u64,
+    },
+}
+
+impl GXTValidationKind {
+    /// Returns whether the problem makes compilation fail, or is only a warning.
+    pub fn severity(&self) -> GXTSeverity {
+        match self {
+            GXTValidationKind::KeyTooLong
+            | GXTValidationKind::HashedKey
+            | GXTValidationKind::InvalidHash
+            | GXTValidationKind::DuplicateName(_)
+            | GXTValidationKind::TableNameTooLong
+            | GXTValidationKind::AuxTableNotAllowed
+            | GXTValidationKind::UnencodableCharacter { .. }
+            | GXTValidationKind::TableTooLarge(_)
+            | GXTValidationKind::FileTooLarge(_)
+            | GXTValidationKind::TooLargeToRead { .. } => GXTSeverity::Error,
+            GXTValidationKind::EmptyKey
+            | GXTValidationKind::InvalidKeyCharacter(_)
+            | GXTValidationKind::InvalidTableNameCharacter(_)
+            | GXTValidationKind::ReservedTableName
+            | GXTValidationKind::EmptyTable
+            | GXTValidationKind::TooManyTables { .. }
+            | GXTValidationKind::StringTooLong { .. }
+            | GXTValidationKind::MissionTableTooLarge { .. } => GXTSeverity::Warning,
+        }
+    }
+}
+
+/// Describes a problem found by [GXTFile::validate], along with its location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTValidationIssue {
+    /// The name of the table (the main table is called [MAIN_TABLE_NAME]).
+    pub table: String,
+    /// The name of the string, or None if the issue concerns the whole table.
+    pub key: Option<String>,
+    /// The problem itself.
+    pub kind: GXTValidationKind,
+}
+
+impl fmt::Display for GXTValidationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GXTValidationKind::EmptyKey => write!(f, "string name is empty"),
+            GXTValidationKind::KeyTooLong => write!(f, "string name is longer than 8 bytes"),
+            GXTValidationKind::InvalidKeyCharacter(c) => write!(f, "string name contains an invalid character {c:?}"),
+            GXTValidationKind::HashedKey => write!(f, "hash-based string names can only be used in San Andreas files"),
+            GXTValidationKind::InvalidHash => write!(f, "hash-based string name does not contain a valid hex value"),
+            GXTValidationKind::DuplicateName(k) => write!(f, "string name or hash is the same as for {k}"),
+            GXTValidationKind::TableNameTooLong => write!(f, "table name is longer than 8 bytes"),
+            GXTValidationKind::InvalidTableNameCharacter(c) => write!(f, "table name contains an invalid character {c:?}"),
+            GXTValidationKind::ReservedTableName => write!(f, "auxiliary table cannot be called {MAIN_TABLE_NAME}"),
+            GXTValidationKind::AuxTableNotAllowed => write!(f, "a GTA III format file cannot have auxiliary tables"),
+            GXTValidationKind::EmptyTable => write!(f, "table has no strings"),
+            GXTValidationKind::UnencodableCharacter { position, character } => {
+                write!(f, "character {character:?} (U+{:04X}) at position {position} cannot be encoded", u32::from(*character))
+            },
+            GXTValidationKind::TableTooLarge(s) => write!(f, "table data is {s} bytes long, which doesn't fit into the file"),
+            GXTValidationKind::FileTooLarge(o) => write!(f, "table starts at offset {o}, which doesn't fit into the file"),
+            GXTValidationKind::TooLargeToRead { what, limit } => write!(f, "more than {limit} {what}, which can't be read back"),
+            GXTValidationKind::TooManyTables { count, limit } => write!(f, "file has {count} tables, the game can only handle {limit}"),
+            GXTValidationKind::StringTooLong { length, limit } => write!(f, "string is {length} characters long, the game can only handle {limit}"),
+            GXTValidationKind::MissionTableTooLarge { size, limit } => write!(f, "table data is {size} bytes long, the game can only handle {limit}"),
+        }
+    }
+}
+
+impl GXTValidationIssue {
+    /// Returns true if the problem makes compilation fail.
+    pub fn is_error(&self) -> bool {
+        self.kind.severity() == GXTSeverity::Error
+    }
+}
+
+impl fmt::Display for GXTValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(k) => write!(f, "[{}] {}: {}", self.table, k, self.kind),
+            None => write!(f, "[{}]: {}", self.table, self.kind),
+        }
+    }
+}
+
+/// Describes a string in an auxiliary table whose name (or hash) is also used in the main table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GXTShadowedKey {
+    /// The name of the auxiliary table.
+    pub table: String,
+    /// The name of the string in the auxiliary table.
+    pub key: String,
+    /// The name of the string in the main table that shadows it. It differs from `key` when only
+    /// their hashes are the same.
+    pub main_key: String,
+}
+
+impl fmt::Display for GXTShadowedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: shadowed by {} in the {} table", self.table, self.key, self.main_key, MAIN_TABLE_NAME)
+    }
+}
+
+// checks a string name, except for duplicates
+fn validate_key(key: &str, format: &GXTFileFormat) -> Option<GXTValidationKind> {
+
+    if key.is_empty() {
+        return Some(GXTValidationKind::EmptyKey);
+    }
+    if let GXTFileFormat::Three | GXTFileFormat::Vice = format
+        && let Some(c) = key.chars().find(|c| !c.is_ascii_graphic()) {
+        return Some(GXTValidationKind::InvalidKeyCharacter(c));
+    }
+
+    let looks_like_hash = key.len() == 9 && key.starts_with('#') && !key.starts_with("##");
+    match format {
+        GXTFileFormat::Three | GXTFileFormat::Vice if looks_like_hash => Some(GXTValidationKind::HashedKey),
+        GXTFileFormat::Three | GXTFileFormat::Vice => {
+            string_to_name_basic(key).err().map(|_| GXTValidationKind::KeyTooLong)
+        },
+        GXTFileFormat::San8 | GXTFileFormat::San16 => {
+            string_to_name(key, format).err().map(|_| GXTValidationKind::InvalidHash)
+        },
+    }
+}
+
+// checks an auxiliary table's name
+fn validate_table_name(name: &str, format: &GXTFileFormat) -> Option<GXTValidationKind> {
+
+    if let GXTFileFormat::Three = format {
+        return Some(GXTValidationKind::AuxTableNotAllowed);
+    }
+    if name == MAIN_TABLE_NAME {
+        return Some(GXTValidationKind::ReservedTableName);
+    }
+    if let Some(c) = name.chars().find(|c| !c.is_ascii_graphic()) {
+        return Some(GXTValidationKind::InvalidTableNameCharacter(c));
+    }
+    if name.len() > 8 {
+        return Some(GXTValidationKind::TableNameTooLong);
+    }
+    None
+}
+
+impl GXTFile {
+    /// Checks whether the file can be compiled with the specified [WriteOptions], returning every
+    /// problem found. If none of them is an error ([GXTValidationIssue::is_error]),
+    /// [GXTFile::write_to_gxt_with_options] will succeed.
+    ///
+    /// Besides the errors that make compilation fail, this also reports warnings: empty tables,
+    /// unusual string and table names, and anything going over the game's limits, if they are set
+    /// in the options.
+    pub fn validate(&self, options: &WriteOptions) -> Vec<GXTValidationIssue> {
+
+        let mut res: Vec<GXTValidationIssue> = vec!();
+
+        let (entry_size, header_size): (u64, u64) = match self.format {
+            GXTFileFormat::Three => (12, 0),
+            GXTFileFormat::Vice => (12, 8 + 12 * (1 + self.aux_tables.len() as u64)),
+            GXTFileFormat::San8 | GXTFileFormat::San16 => (8, 4 + 8 + 12 * (1 + self.aux_tables.len() as u64)),
+        };
+        let mut table_offset: u64 = header_size;
+        let limits = options.limits.unwrap_or_default();
+        // the reader's limits apply to the whole file, and are reported once, where they're exceeded
+        let mut string_count: usize = 0;
+        let mut total_length: usize = 0;
+
+        for (i, (table, strings)) in self.tables().enumerate() {
+
+            // an auxiliary table may be wrongly called MAIN as well
+            let is_main = i == 0;
+            let mut issue = |key: Option<&String>, kind| res.push(GXTValidationIssue { table: table.to_string(), key: key.cloned(), kind });
+
+            if let Some(kind) = validate_table_name(table, &self.format).filter(|_| !is_main) {
+                issue(None, kind);
+            }
+            if strings.is_empty() {
+                issue(None, GXTValidationKind::EmptyTable);
+            }
+            if let Some(limit) = limits.max_tables.filter(|l| *l == i) {
+                issue(None, GXTValidationKind::TooManyTables { count: 1 + self.aux_tables.len(), limit });
+            }
+            if i == MAX_TABLES {
+                issue(None, GXTValidationKind::TooLargeToRead { what: "tables", limit: MAX_TABLES });
+            }
+            let over_strings = string_count > MAX_ENTRIES;
+            let over_length = total_length > MAX_TOTAL_LENGTH;
+
+            let mut names: HashMap<String, &String> = HashMap::new();
+            let mut tdat_size: u64 = 0;
+            let mut values: HashSet<&String> = HashSet::new();
+
+            for (key, value) in strings {
+
+                match validate_key(key, &self.format) {
+                    Some(kind) => { issue(Some(key), kind); },
+                    None => {
+                        let name = string_to_name(key, &self.format).map(|n| n.to_string()).unwrap_or_default();
+                        match names.get(&name) {
+                            Some(other) => { issue(Some(key), GXTValidationKind::DuplicateName(other.to_string())); },
+                            None => { names.insert(name, key); },
+                        }
+                    },
+                }
+
+                let mut substitutions: Vec<GXTSubstitution> = vec!();
+                let (codes, failures) = encode_codes(value, &self.format, options, &mut substitutions);
+                for (position, character, _) in failures {
+                    issue(Some(key), GXTValidationKind::UnencodableCharacter { position, character });
+                }
+                if let Some(limit) = limits.max_string_chars.filter(|l| codes.len() > *l) {
+                    issue(Some(key), GXTValidationKind::StringTooLong { length: codes.len(), limit });
+                }
+                if codes.len() > MAX_STRING_LENGTH {
+                    issue(Some(key), GXTValidationKind::TooLargeToRead { what: "characters in a string", limit: MAX_STRING_LENGTH });
+                }
+                total_length += codes.len();
+
+                // identical strings share their data
+                if !values.insert(value) {
+                    continue;
+                }
+                tdat_size += match self.format {
+                    GXTFileFormat::San8 => codes.len() as u64 + 1,
+                    _ => 2 * (codes.len() as u64 + 1),
+                };
+            }
+
+            string_count += strings.len();
+            if !over_strings && string_count > MAX_ENTRIES {
+                issue(None, GXTValidationKind::TooLargeToRead { what: "strings", limit: MAX_ENTRIES });
+            } else if !over_length && total_length > MAX_TOTAL_LENGTH {
+                issue(None, GXTValidationKind::TooLargeToRead { what: "characters of text", limit: MAX_TOTAL_LENGTH });
+            }
+
+            if let GXTFileFormat::San8 | GXTFileFormat::San16 = self.format {
+                tdat_size = tdat_size.next_multiple_of(4);
+            }
+            if let Some(limit) = limits.max_mission_table_bytes.filter(|l| !is_main && tdat_size > *l) {
+                issue(None, GXTValidationKind::MissionTableTooLarge { size: tdat_size, limit });
+            }
+            if tdat_size > u32::MAX as u64 {
+                issue(None, GXTValidationKind::TableTooLarge(tdat_size));
+            }
+            if table_offset > u32::MAX as u64 {
+                issue(None, GXTValidationKind::FileTooLarge(table_offset));
+            }
+
+            let table_name_size = if is_main { 0 } else { 8 };
+            table_offset += table_name_size + 8 + entry_size * strings.len() as u64 + 8 + tdat_size;
+        }
+
+        res
+    }
+}
+
+impl GXTFile {
+    /// Lists the strings in auxiliary tables that have the same name (or, in San Andreas, the same
+    /// hash) as a string in the main table. The games look strings up in the main table first, so
+    /// such strings are never shown, even while their mission table is loaded.
+    ///
+    /// Names that can't be compiled are skipped; [GXTFile::validate] reports those.
+    pub fn shadowed_keys(&self) -> Vec<GXTShadowedKey> {
+
+        let main_names: HashMap<String, &String> = self.main_table.keys()
+            .filter_map(|k| string_to_name(k, &self.format).ok().map(|n| (n.to_string(), k)))
+            .collect();
+
+        let mut res: Vec<GXTShadowedKey> = vec!();
+
+        for (table, strings) in &self.aux_tables {
+            for key in strings.keys() {
+                let Ok(name) = string_to_name(key, &self.format) else { continue; };
+                if let Some(main_key) = main_names.get(&name.to_string()) {
+                    res.push(GXTShadowedKey { table: table.clone(), key: key.clone(), main_key: main_key.to_string() });
+                }
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+    use super::*;
+
+    #[test]
+    fn validate_vice_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([
+                ("GOOD".to_string(),"Fine ~r~red~w~".to_string()),
+                ("TOOLONGNAME".to_string(),"Fine".to_string()),
+                ("#0123ABCD".to_string(),"Fine".to_string()),
+                ("BAD CHAR".to_string(),"Fine".to_string()),
+                ("TEXT".to_string(),"Łódź and Kraków".to_string()),
+            ]),
+            IndexMap::from([
+                ("MAIN".to_string(), IndexMap::from([("X".to_string(),"X".to_string())])),
+                ("LONGTABLE".to_string(), IndexMap::new()),
+            ]),
+            );
+
+        let issues = x.validate(&Default::default());
+        let kinds: Vec<(&str, Option<&str>, &GXTValidationKind)> = issues.iter().map(|i| (i.table.as_str(), i.key.as_deref(), &i.kind)).collect();
+
+        assert!( kinds == vec!(
+            ("MAIN", Some("TOOLONGNAME"), &GXTValidationKind::KeyTooLong),
+            ("MAIN", Some("#0123ABCD"), &GXTValidationKind::HashedKey),
+            ("MAIN", Some("BAD CHAR"), &GXTValidationKind::InvalidKeyCharacter(' ')),
+            ("MAIN", Some("TEXT"), &GXTValidationKind::UnencodableCharacter { position: 0, character: 'Ł' }),
+            ("MAIN", Some("TEXT"), &GXTValidationKind::UnencodableCharacter { position: 3, character: 'ź' }),
+            ("MAIN", None, &GXTValidationKind::ReservedTableName),
+            ("LONGTABLE", None, &GXTValidationKind::TableNameTooLong),
+            ("LONGTABLE", None, &GXTValidationKind::EmptyTable),
+        ));
+        assert!( issues[3].to_string() == "[MAIN] TEXT: character 'Ł' (U+0141) at position 0 cannot be encoded" );
+        assert!( issues.iter().filter(|i| !i.is_error()).map(|i| i.key.as_deref()).eq([Some("BAD CHAR"), None, None]) );
+
+        // a fallback policy takes care of the characters
+        let options = WriteOptions {
+            fallback: Some(crate::GXTFallbackPolicy { strip_diacritics: true, transliteration: HashMap::from([('Ł', "L".to_string())]) }),
+            ..Default::default()
+        };
+        assert!( x.validate(&options).len() == 6 );
+    }
+
+    #[test]
+    fn validate_sa_test() {
+
+        let hash = format!("#{:08X}", crate::string_to_name_crc32("FOO").unwrap());
+        let x = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([
+                ("FOO".to_string(),"Foo".to_string()),
+                (hash.clone(),"Also foo".to_string()),
+                ("#NOTAHASH".to_string(),"Bar".to_string()),
+                ("LONG_NAMES_ARE_FINE".to_string(),"€".to_string()),
+                ("SPACES ARE FINE".to_string(),"Baz".to_string()),
+            ]),
+            IndexMap::new(),
+            );
+
+        let issues = x.validate(&Default::default());
+        assert!( issues == vec!(
+            GXTValidationIssue { table: "MAIN".to_string(), key: Some(hash), kind: GXTValidationKind::DuplicateName("FOO".to_string()) },
+            GXTValidationIssue { table: "MAIN".to_string(), key: Some("#NOTAHASH".to_string()), kind: GXTValidationKind::InvalidHash },
+        ));
+
+        let three = GXTFile::new(GXTFileFormat::Three, IndexMap::from([("A".to_string(),"A".to_string())]),
+            IndexMap::from([("AUX".to_string(), IndexMap::from([("B".to_string(),"B".to_string())]))]));
+        assert!( three.validate(&Default::default()) == vec!(
+            GXTValidationIssue { table: "AUX".to_string(), key: None, kind: GXTValidationKind::AuxTableNotAllowed },
+        ));
+
+        // strings longer than the reader accepts can't be compiled
+        let long = GXTFile::new(GXTFileFormat::San8, IndexMap::from([("LONG".to_string(),"a".repeat(MAX_STRING_LENGTH+1))]), IndexMap::new());
+        let issues = long.validate(&Default::default());
+        assert!( issues.len() == 1 && issues[0].is_error() );
+        assert!( issues[0].to_string() == format!("[MAIN] LONG: more than {MAX_STRING_LENGTH} characters in a string, which can't be read back") );
+    }
+
+    #[test]
+    fn shadowed_keys_test() {
+
+        let hash = format!("#{:08X}", crate::string_to_name_crc32("BYE").unwrap());
+        let x = GXTFile::new(
+            GXTFileFormat::San8,
+            IndexMap::from([("HELLO".to_string(),"Hello".to_string()),("BYE".to_string(),"Bye".to_string())]),
+            IndexMap::from([("AUX1".to_string(), IndexMap::from([
+                ("HELLO".to_string(),"Hello again".to_string()),
+                (hash.clone(),"Bye again".to_string()),
+                ("OTHER".to_string(),"Other".to_string()),
+            ]))]),
+            );
+
+        assert!( x.shadowed_keys() == vec!(
+            GXTShadowedKey { table: "AUX1".to_string(), key: "HELLO".to_string(), main_key: "HELLO".to_string() },
+            GXTShadowedKey { table: "AUX1".to_string(), key: hash, main_key: "BYE".to_string() },
+        ));
+    }
+
+    #[test]
+    fn limits_test() {
+
+        let x = GXTFile::new(
+            GXTFileFormat::Vice,
+            IndexMap::from([("LONG".to_string(),"a".repeat(300)),("SHORT".to_string(),"a".to_string())]),
+            IndexMap::from([
+                ("AUX1".to_string(), IndexMap::from([("BIG".to_string(),"a".repeat(200))])),
+                ("AUX2".to_string(), IndexMap::from([("SMALL".to_string(),"a".to_string())])),
+            ]),
+            );
+
+        assert!( x.validate(&Default::default()).is_empty() );
+
+        // the mission table's TDAT is 402 bytes long
+        let custom = GXTLimits { max_tables: Some(2), max_mission_table_bytes: Some(400), ..Default::default() };
+        assert!( GXTLimitProfile::Vice.limits() == GXTLimits::default() );
+        assert!( GXTLimitProfile::Three.estimated_limits().max_tables == Some(1) );
+        let limits = GXTLimitProfile::Vice.estimated_limits().override_with(&custom);
+        assert!( limits == GXTLimits { max_tables: Some(2), max_string_chars: Some(255), max_mission_table_bytes: Some(400) } );
+
+        let options = WriteOptions { limits: Some(limits), ..Default::default() };
+        assert!( x.validate(&options) == vec!(
+            GXTValidationIssue { table: "MAIN".to_string(), key: Some("LONG".to_string()), kind: GXTValidationKind::StringTooLong { length: 300, limit: 255 } },
+            GXTValidationIssue { table: "AUX1".to_string(), key: None, kind: GXTValidationKind::MissionTableTooLarge { size: 402, limit: 400 } },
+            GXTValidationIssue { table: "AUX2".to_string(), key: None, kind: GXTValidationKind::TooManyTables { count: 3, limit: 2 } },
+        ));
+        // the file still compiles, it may just not work in the game
+        assert!( x.validate(&options).iter().all(|i| !i.is_error()) );
+
+        let custom = read_limits(&mut std::io::Cursor::new("max_string_chars = 500\n")).unwrap();
+        assert!( custom == GXTLimits { max_string_chars: Some(500), ..Default::default() } );
+    }
+}